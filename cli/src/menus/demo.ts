@@ -198,23 +198,8 @@ const runCompleteWorkflowDemo = async () => {
   displayResponse('Order created', fullOrder);
   await sleep(1000);
   
-  // Step 4: Lock funds for the order
-  formatStepHeader(4, 'Locking funds for the order');
-  const lock = await callApi('post', '/payments/lock', {
-    address: fullTenantAddress,
-    amount: 50
-  });
-  
-  if (!lock) {
-    console.log(chalk.red('Demo workflow failed at locking funds.'));
-    return;
-  }
-  
-  displayResponse('Funds locked', lock);
-  await sleep(1000);
-  
-  // Step 5: Provider places bid
-  formatStepHeader(5, 'Provider places bid on the order');
+  // Step 4: Provider places bid
+  formatStepHeader(4, 'Provider places bid on the order');
   const fullBid = await callApi('post', `/orders/${fullOrder.id}/bids`, {
     providerId: fullProvider.id,
     bidPrice: 8.5
@@ -228,8 +213,8 @@ const runCompleteWorkflowDemo = async () => {
   displayResponse('Bid placed', fullBid);
   await sleep(1000);
   
-  // Step 6: Tenant accepts bid
-  formatStepHeader(6, 'Tenant accepts the bid');
+  // Step 5: Tenant accepts bid
+  formatStepHeader(5, 'Tenant accepts the bid');
   const fullAcceptedBid = await callApi('put', `/bids/${fullBid.id}/accept`);
   
   if (!fullAcceptedBid) {
@@ -240,8 +225,8 @@ const runCompleteWorkflowDemo = async () => {
   displayResponse('Bid accepted and lease created', fullAcceptedBid);
   await sleep(1000);
   
-  // Step 7: Check deployment status
-  formatStepHeader(7, 'Checking deployment status');
+  // Step 6: Check deployment status
+  formatStepHeader(6, 'Checking deployment status');
   const deployment = await callApi('get', `/deployments/${fullAcceptedBid.leaseId}`);
   
   if (!deployment) {
@@ -252,11 +237,9 @@ const runCompleteWorkflowDemo = async () => {
   displayResponse('Deployment status', deployment);
   await sleep(1000);
   
-  // Step 8: Process payment from tenant to provider
-  formatStepHeader(8, 'Processing payment from tenant to provider');
-  const payment = await callApi('post', `/leases/${fullAcceptedBid.leaseId}/payment`, {
-    amount: 8.5
-  });
+  // Step 7: Process payment from tenant to provider
+  formatStepHeader(7, 'Processing payment from tenant to provider');
+  const payment = await callApi('post', `/leases/${fullAcceptedBid.leaseId}/payment`);
   
   if (!payment) {
     console.log(chalk.red('Demo workflow failed at processing payment.'));
@@ -266,8 +249,8 @@ const runCompleteWorkflowDemo = async () => {
   displayResponse('Payment processed', payment);
   await sleep(1000);
   
-  // Step 9: Provider withdraws earnings
-  formatStepHeader(9, 'Provider withdraws earnings');
+  // Step 8: Provider withdraws earnings
+  formatStepHeader(8, 'Provider withdraws earnings');
   const withdraw = await callApi('post', '/payments/withdraw', {
     address: fullProviderAddress
   });
@@ -280,8 +263,8 @@ const runCompleteWorkflowDemo = async () => {
   displayResponse('Earnings withdrawn', withdraw);
   await sleep(1000);
   
-  // Step 10: Complete the lease
-  formatStepHeader(10, 'Completing the lease');
+  // Step 9: Complete the lease
+  formatStepHeader(9, 'Completing the lease');
   const completedLease = await callApi('put', `/leases/${fullAcceptedBid.leaseId}/complete`);
   
   if (!completedLease) {
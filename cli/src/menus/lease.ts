@@ -53,38 +53,17 @@ export const displayLeaseMenu = async () => {
       break;
     
     case 'Create Lease':
-      const leaseData = await inquirer.prompt([
+      const { newLeaseId } = await inquirer.prompt([
         {
           type: 'input',
-          name: 'orderId',
-          message: chalk.cyan('Enter order ID:'),
-          validate: validateNotEmpty
-        },
-        {
-          type: 'input',
-          name: 'providerId',
-          message: chalk.cyan('Enter provider ID:'),
-          validate: validateNotEmpty
-        },
-        {
-          type: 'input',
-          name: 'startBlock',
-          message: chalk.cyan('Enter start block number:'),
-          validate: validateNumber
-        },
-        {
-          type: 'input',
-          name: 'endBlock',
-          message: chalk.cyan('Enter end block number:'),
+          name: 'newLeaseId',
+          message: chalk.cyan('Enter lease ID returned by the accepted bid:'),
           validate: validateNumber
         }
       ]);
       
       const newLease = await callApi('post', '/leases', {
-        orderId: parseInt(leaseData.orderId),
-        providerId: parseInt(leaseData.providerId),
-        startBlock: parseInt(leaseData.startBlock),
-        endBlock: parseInt(leaseData.endBlock)
+        leaseId: parseInt(newLeaseId)
       });
       
       if (newLease) {
@@ -125,31 +104,16 @@ export const displayLeaseMenu = async () => {
       break;
     
     case 'Process Lease Payment':
-      const paymentData = await inquirer.prompt([
+      const { paymentLeaseId } = await inquirer.prompt([
         {
           type: 'input',
-          name: 'leaseId',
+          name: 'paymentLeaseId',
           message: chalk.cyan('Enter lease ID:'),
           validate: validateNotEmpty
-        },
-        {
-          type: 'input',
-          name: 'amount',
-          message: chalk.cyan('Enter payment amount (AKT):'),
-          validate: validateNumber
-        },
-        {
-          type: 'input',
-          name: 'tokenAddress',
-          message: chalk.cyan('Enter token address (optional):'),
-          default: ''
         }
       ]);
       
-      const leasePayment = await callApi('post', `/leases/${paymentData.leaseId}/payment`, {
-        amount: parseFloat(paymentData.amount),
-        tokenAddress: paymentData.tokenAddress || null
-      });
+      const leasePayment = await callApi('post', `/leases/${paymentLeaseId}/payment`);
       
       if (leasePayment) {
         displayResponse('Lease Payment Processed', leasePayment);
@@ -170,14 +170,22 @@ export const displayOrderMenu = async () => {
         {
           type: 'input',
           name: 'bidPrice',
-          message: chalk.cyan('Enter bid price (AKT):'),
+          message: chalk.cyan('Enter bid price per instance (AKT):'),
+          validate: validateNumber
+        },
+        {
+          type: 'input',
+          name: 'quantity',
+          message: chalk.cyan('Enter number of instances:'),
+          default: '1',
           validate: validateNumber
         }
       ]);
       
       const newBid = await callApi('post', `/orders/${bidData.orderId}/bids`, {
         providerId: bidData.providerId,
-        bidPrice: parseFloat(bidData.bidPrice)
+        bidPrice: parseFloat(bidData.bidPrice),
+        quantity: parseInt(bidData.quantity)
       });
       
       if (newBid) {
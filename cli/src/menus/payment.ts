@@ -16,7 +16,6 @@ export const displayPaymentMenu = async () => {
         'List Payments', 
         'Get Payment Details',
         'Deposit Funds',
-        'Lock Funds',
         'Withdraw Provider Earnings',
         'Get Tenant Balance',
         'Get Provider Earnings',
@@ -85,39 +84,6 @@ export const displayPaymentMenu = async () => {
       }
       break;
     
-    case 'Lock Funds':
-      const lockData = await inquirer.prompt([
-        {
-          type: 'input',
-          name: 'address',
-          message: chalk.cyan('Enter tenant address:'),
-          validate: validateNotEmpty
-        },
-        {
-          type: 'input',
-          name: 'amount',
-          message: chalk.cyan('Enter amount to lock:'),
-          validate: validateNumber
-        },
-        {
-          type: 'input',
-          name: 'tokenAddress',
-          message: chalk.cyan('Enter token address (optional, leave empty for AKT):'),
-          default: ''
-        }
-      ]);
-      
-      const lock = await callApi('post', '/payments/lock', {
-        address: lockData.address,
-        amount: parseFloat(lockData.amount),
-        tokenAddress: lockData.tokenAddress || null
-      });
-      
-      if (lock) {
-        displayResponse('Funds Locked', lock);
-      }
-      break;
-    
     case 'Withdraw Provider Earnings':
       const withdrawData = await inquirer.prompt([
         {
@@ -3,22 +3,36 @@ use core::panic;
 
 use soroban_sdk::{contract, contractimpl, contracttype, token, Address, Env};
 
-use provider_registry;
+mod provider_registry {
+    soroban_sdk::contractimport!(
+        file = "../../target/wasm32-unknown-unknown/release/provider_registry.wasm"
+    );
+}
 
 #[contract]
 pub struct EscrowContract;
 
+// Highest protocol fee the admin can set, in basis points.
+pub const MAX_PROTOCOL_FEE_BPS: u32 = 1_000;
+
 #[contracttype]
 pub struct Tenant {
-    locked_balance: i128,
-    unlocked_balance: i128,
+    pub locked_balance: i128,
+    pub unlocked_balance: i128,
 }
 
 #[contracttype]
 pub struct ProviderToken {
-    earned: i128,
-    withdrawn: i128,
-    balance: i128,
+    pub earned: i128,
+    pub withdrawn: i128,
+    pub balance: i128,
+}
+
+#[contracttype]
+pub struct TreasuryToken {
+    pub collected: i128,
+    pub withdrawn: i128,
+    pub balance: i128,
 }
 
 // strorage
@@ -28,14 +42,31 @@ pub enum DataKey {
     TenantBalance(Address, Address),
     ProviderEarnings(u64, Address),
     ProviderRegistry,
+    Admin,
+    OrderBook,
+    Treasury,
+    ProtocolFeeBps,
+    ProtocolFees(Address),
+    // fee owed on a provider's payments in a token below one token unit,
+    // scaled by 10_000
+    FeeRemainder(u64, Address),
 }
 
 #[contractimpl]
 impl EscrowContract {
-    pub fn __constructor(env: Env, provider_registry: Address) {
+    pub fn __constructor(env: Env, provider_registry: Address, admin: Address) {
         env.storage()
             .instance()
             .set(&DataKey::ProviderRegistry, &provider_registry);
+        env.storage().instance().set(&DataKey::Admin, &admin);
+    }
+
+    pub fn set_order_book(env: Env, order_book: Address) {
+        EscrowContract::get_admin(env.clone()).require_auth();
+
+        env.storage()
+            .instance()
+            .set(&DataKey::OrderBook, &order_book);
     }
 
     pub fn deposit(env: Env, token_address: Address, from: Address, amount: i128) {
@@ -79,10 +110,25 @@ impl EscrowContract {
             .set(&DataKey::TenantBalance(to, token_address), &curr_balance);
     }
 
+    // Locks a tenant's funds for an order on behalf of the order book, with
+    // the tenant's consent. Funds can only be released by the order book, so
+    // it is the only caller.
     pub fn lock(env: Env, token_address: Address, from: Address, amount: i128) {
-        // Require authorization from the sender
+        EscrowContract::get_order_book(env.clone()).require_auth();
         from.require_auth();
 
+        EscrowContract::lock_balance(env, token_address, from, amount);
+    }
+
+    // Locks a tenant's funds on behalf of the order book, for charges the
+    // tenant agreed to in advance such as lease auto-renewals.
+    pub fn lock_from_order_book(env: Env, token_address: Address, from: Address, amount: i128) {
+        EscrowContract::get_order_book(env.clone()).require_auth();
+
+        EscrowContract::lock_balance(env, token_address, from, amount);
+    }
+
+    fn lock_balance(env: Env, token_address: Address, from: Address, amount: i128) {
         let mut curr_balance: Tenant = env
             .storage()
             .instance()
@@ -101,7 +147,10 @@ impl EscrowContract {
             .set(&DataKey::TenantBalance(from, token_address), &curr_balance);
     }
 
+    // Releases locked funds back to their owner on behalf of the order book.
     pub fn unlock_tokens(env: Env, token_address: Address, from: Address, amount: i128) {
+        EscrowContract::get_order_book(env.clone()).require_auth();
+
         let mut curr_balance: Tenant = env
             .storage()
             .instance()
@@ -120,6 +169,7 @@ impl EscrowContract {
             .set(&DataKey::TenantBalance(from, token_address), &curr_balance);
     }
 
+    // Pays locked funds to a provider's earnings on behalf of the order book.
     pub fn transfer_locked(
         env: Env,
         token_address: Address,
@@ -127,6 +177,8 @@ impl EscrowContract {
         amount: i128,
         provider: u64,
     ) {
+        EscrowContract::get_order_book(env.clone()).require_auth();
+
         let curr_balance: Tenant = env
             .storage()
             .instance()
@@ -149,6 +201,36 @@ impl EscrowContract {
             },
         );
 
+        // the protocol fee is kept in escrow for the treasury. Fractions of a
+        // unit carry over to the provider's next payment, so splitting payments
+        // does not round the fee away
+        let remainder_key = DataKey::FeeRemainder(provider, token_address.clone());
+        let scaled_fee = amount * EscrowContract::get_protocol_fee(env.clone()) as i128
+            + env
+                .storage()
+                .instance()
+                .get(&remainder_key)
+                .unwrap_or(0i128);
+        let fee = scaled_fee / 10_000;
+        env.storage()
+            .instance()
+            .set(&remainder_key, &(scaled_fee % 10_000));
+        if fee > 0 {
+            env.storage().instance().update(
+                &DataKey::ProtocolFees(token_address.clone()),
+                |opt_fees: Option<TreasuryToken>| {
+                    let mut fees = opt_fees.unwrap_or(TreasuryToken {
+                        collected: 0,
+                        withdrawn: 0,
+                        balance: 0,
+                    });
+                    fees.collected += fee;
+                    fees.balance += fee;
+                    fees
+                },
+            );
+        }
+
         env.storage().instance().update(
             &DataKey::ProviderEarnings(provider, token_address),
             |opt_balance: Option<ProviderToken>| {
@@ -157,8 +239,91 @@ impl EscrowContract {
                     withdrawn: 0,
                     balance: 0,
                 });
-                balance.earned += amount;
-                balance.balance += amount;
+                balance.earned += amount - fee;
+                balance.balance += amount - fee;
+                balance
+            },
+        );
+    }
+
+    // Sets the share of every lease payment kept as protocol fee, in basis
+    // points.
+    pub fn set_protocol_fee(env: Env, fee_bps: u32) {
+        EscrowContract::get_admin(env.clone()).require_auth();
+
+        if fee_bps > MAX_PROTOCOL_FEE_BPS {
+            panic!("Protocol fee exceeds {} bps", MAX_PROTOCOL_FEE_BPS);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::ProtocolFeeBps, &fee_bps);
+    }
+
+    pub fn set_treasury(env: Env, treasury: Address) {
+        EscrowContract::get_admin(env.clone()).require_auth();
+
+        env.storage().instance().set(&DataKey::Treasury, &treasury);
+    }
+
+    // Sends collected fees in the given token to the treasury.
+    pub fn withdraw_protocol_fees(env: Env, token_address: Address, amount: i128) {
+        EscrowContract::get_admin(env.clone()).require_auth();
+
+        let mut fees = EscrowContract::get_protocol_fees(env.clone(), token_address.clone());
+        if fees.balance < amount {
+            panic!("Insufficient balance");
+        }
+
+        let token_client = token::Client::new(&env, &token_address);
+        token_client.transfer(
+            &env.current_contract_address(),
+            &EscrowContract::get_treasury(env.clone()),
+            &amount,
+        );
+
+        fees.withdrawn += amount;
+        fees.balance -= amount;
+        env.storage()
+            .instance()
+            .set(&DataKey::ProtocolFees(token_address), &fees);
+    }
+
+    // Moves locked funds of one account to the unlocked balance of another on
+    // behalf of the order book, such as a forfeited provider bond.
+    pub fn transfer_locked_to(
+        env: Env,
+        token_address: Address,
+        from: Address,
+        amount: i128,
+        to: Address,
+    ) {
+        EscrowContract::get_order_book(env.clone()).require_auth();
+
+        let mut from_balance: Tenant = env
+            .storage()
+            .instance()
+            .get(&DataKey::TenantBalance(from.clone(), token_address.clone()))
+            .unwrap();
+
+        if from_balance.locked_balance < amount {
+            panic!("Insufficient balance");
+        }
+
+        from_balance.locked_balance -= amount;
+        env.storage().instance().set(
+            &DataKey::TenantBalance(from, token_address.clone()),
+            &from_balance,
+        );
+
+        env.storage().instance().update(
+            &DataKey::TenantBalance(to, token_address),
+            |opt_balance: Option<Tenant>| {
+                let mut balance = opt_balance.unwrap_or(Tenant {
+                    locked_balance: 0,
+                    unlocked_balance: 0,
+                });
+                balance.unlocked_balance += amount;
                 balance
             },
         );
@@ -175,13 +340,12 @@ impl EscrowContract {
             .unwrap_or_else(|| panic!("Provider registry contract is not found"));
 
         // get provider
-        let provider_registry_client =
-            provider_registry::ProviderRegistryContractClient::new(&env, &provider_registry);
+        let provider_registry_client = provider_registry::Client::new(&env, &provider_registry);
         let (provider_id, _) = provider_registry_client.get_provider_by_address(&from);
 
         let mut provider_earnings: ProviderToken = env
             .storage()
-            .temporary()
+            .instance()
             .get(&DataKey::ProviderEarnings(
                 provider_id,
                 token_address.clone(),
@@ -235,6 +399,45 @@ impl EscrowContract {
             .get(&DataKey::ProviderRegistry)
             .unwrap_or_else(|| panic!("Provider registry contract is not found"))
     }
+
+    pub fn get_admin(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .unwrap_or_else(|| panic!("Admin is not found"))
+    }
+
+    pub fn get_protocol_fee(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::ProtocolFeeBps)
+            .unwrap_or(0)
+    }
+
+    pub fn get_protocol_fees(env: Env, token_address: Address) -> TreasuryToken {
+        env.storage()
+            .instance()
+            .get(&DataKey::ProtocolFees(token_address))
+            .unwrap_or(TreasuryToken {
+                collected: 0,
+                withdrawn: 0,
+                balance: 0,
+            })
+    }
+
+    pub fn get_treasury(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Treasury)
+            .unwrap_or_else(|| panic!("Treasury is not found"))
+    }
+
+    pub fn get_order_book(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::OrderBook)
+            .unwrap_or_else(|| panic!("Order book contract is not found"))
+    }
 }
 
 mod test;
@@ -1,18 +1,13 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::{testutils::Address as _, Env};
+use soroban_sdk::{
+    testutils::{Address as _, MockAuth, MockAuthInvoke},
+    Env, IntoVal,
+};
 use token::Client as TokenClient;
 use token::StellarAssetClient as TokenAdminClient;
 
-use provider_registry;
-
-mod test_provider_registry {
-    soroban_sdk::contractimport!(
-        file = "../../target/wasm32-unknown-unknown/release/provider_registry.wasm",
-    );
-}
-
 // Helper function to create a mock token contract
 fn create_token_contract<'a>(e: &Env, admin: &Address) -> (TokenClient<'a>, TokenAdminClient<'a>) {
     let sac = e.register_stellar_asset_contract_v2(admin.clone());
@@ -23,9 +18,9 @@ fn create_token_contract<'a>(e: &Env, admin: &Address) -> (TokenClient<'a>, Toke
 }
 
 // Helper function to setup a mock provider registry
-fn setup_provider_registry(e: &Env) -> (Address, test_provider_registry::Client) {
-    let contract_id = e.register(provider_registry::ProviderRegistryContract, {});
-    let client = test_provider_registry::Client::new(e, &contract_id);
+fn setup_provider_registry(e: &Env) -> (Address, provider_registry::Client<'_>) {
+    let contract_id = e.register(::provider_registry::ProviderRegistryContract, ());
+    let client = provider_registry::Client::new(e, &contract_id);
     (contract_id, client)
 }
 
@@ -34,10 +29,100 @@ fn test_constructor() {
     let env = Env::default();
     let (provider_registry_id, _) = setup_provider_registry(&env);
 
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let admin = Address::generate(&env);
+
+    let contract_id = env.register(EscrowContract, (&provider_registry_id, &admin));
     let client = EscrowContractClient::new(&env, &contract_id);
 
     assert_eq!(provider_registry_id, client.get_provider_registry());
+    assert_eq!(admin, client.get_admin());
+}
+
+#[test]
+fn test_lock_from_order_book() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (provider_registry_id, _) = setup_provider_registry(&env);
+
+    // Create token contract
+    let token_admin = Address::generate(&env);
+    let (token, token_admin_client) = create_token_contract(&env, &token_admin);
+    let token_id = token.address.clone();
+
+    // Create escrow contract and register the order book
+    let admin = Address::generate(&env);
+    let contract_id = env.register(EscrowContract, (&provider_registry_id, &admin));
+    let client = EscrowContractClient::new(&env, &contract_id);
+
+    let order_book = Address::generate(&env);
+    client.set_order_book(&order_book);
+    assert_eq!(client.get_order_book(), order_book);
+
+    // Setup user
+    let user = Address::generate(&env);
+    token_admin_client.mint(&user, &1000);
+    client.deposit(&token_id, &user, &500);
+
+    // Lock tokens on behalf of the order book
+    client.lock_from_order_book(&token_id, &user, &300);
+
+    let balance = client.get_tenant_balance(&token_id, &user);
+    assert_eq!(balance.unlocked_balance, 200);
+    assert_eq!(balance.locked_balance, 300);
+}
+
+#[test]
+fn test_transfer_locked_to() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (provider_registry_id, _) = setup_provider_registry(&env);
+    let token_admin = Address::generate(&env);
+    let (token, token_admin_client) = create_token_contract(&env, &token_admin);
+    let token_id = token.address.clone();
+
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
+    let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
+
+    let from = Address::generate(&env);
+    let to = Address::generate(&env);
+    token_admin_client.mint(&from, &1000);
+    client.deposit(&token_id, &from, &500);
+    client.lock(&token_id, &from, &300);
+
+    client.transfer_locked_to(&token_id, &from, &100, &to);
+
+    let balance = client.get_tenant_balance(&token_id, &from);
+    assert_eq!(balance.locked_balance, 200);
+    assert_eq!(balance.unlocked_balance, 200);
+
+    let balance = client.get_tenant_balance(&token_id, &to);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 100);
+}
+
+#[test]
+#[should_panic(expected = "Order book contract is not found")]
+fn test_lock_from_order_book_not_set() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (provider_registry_id, _) = setup_provider_registry(&env);
+    let token_admin = Address::generate(&env);
+    let (token, _) = create_token_contract(&env, &token_admin);
+
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
+    let client = EscrowContractClient::new(&env, &contract_id);
+
+    client.lock_from_order_book(&token.address, &Address::generate(&env), &300);
 }
 
 #[test]
@@ -53,7 +138,10 @@ fn test_deposit() {
     let token_id = token.address.clone();
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
 
     // Setup user
@@ -91,7 +179,10 @@ fn test_withdraw() {
     let token_id = token.address.clone();
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
 
     // Setup user
@@ -130,7 +221,10 @@ fn test_withdraw_insufficient_balance() {
     let token_id = token.address.clone();
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
 
     // Setup user
@@ -158,8 +252,12 @@ fn test_lock_tokens() {
     let token_id = token.address.clone();
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
 
     // Setup user
     let user = Address::generate(&env);
@@ -193,7 +291,10 @@ fn test_lock_insufficient_balance() {
     let token_id = token.address.clone();
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
 
     // Setup user
@@ -221,8 +322,12 @@ fn test_unlock_tokens() {
     let token_id = token.address.clone();
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
 
     // Setup user
     let user = Address::generate(&env);
@@ -257,8 +362,12 @@ fn test_unlock_insufficient_balance() {
     let token_id = token.address.clone();
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
 
     // Setup user
     let user = Address::generate(&env);
@@ -286,8 +395,12 @@ fn test_transfer_locked() {
     let token_id = token.address.clone();
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
 
     // Setup user and provider
     let user = Address::generate(&env);
@@ -317,13 +430,157 @@ fn test_transfer_locked() {
     assert_eq!(provider_earnings.balance, 300); // This should be updated accordingly
 }
 
+#[test]
+fn test_locked_funds_move_only_for_order_book() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (provider_registry_id, _) = setup_provider_registry(&env);
+    let token_admin = Address::generate(&env);
+    let (token, token_admin_client) = create_token_contract(&env, &token_admin);
+    let token_id = token.address.clone();
+
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
+    let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
+
+    let user = Address::generate(&env);
+    token_admin_client.mint(&user, &1000);
+    client.deposit(&token_id, &user, &1000);
+    client.lock(&token_id, &user, &500);
+
+    // The tenant alone can not lock funds the order book would never release
+    env.mock_auths(&[MockAuth {
+        address: &user,
+        invoke: &MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "lock",
+            args: (&token_id, &user, 100_i128).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client.try_lock(&token_id, &user, &100).is_err());
+
+    // Without the order book's authorization nobody can move locked funds
+    env.set_auths(&[]);
+    assert!(client
+        .try_transfer_locked(&token_id, &user, &500, &42)
+        .is_err());
+    assert!(client.try_unlock_tokens(&token_id, &user, &500).is_err());
+
+    let balance = client.get_tenant_balance(&token_id, &user);
+    assert_eq!(balance.locked_balance, 500);
+    assert_eq!(balance.unlocked_balance, 500);
+}
+
+#[test]
+fn test_protocol_fee() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (provider_registry_id, _) = setup_provider_registry(&env);
+    let token_admin = Address::generate(&env);
+    let (token, token_admin_client) = create_token_contract(&env, &token_admin);
+    let token_id = token.address.clone();
+
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
+    let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
+
+    let treasury = Address::generate(&env);
+    client.set_treasury(&treasury);
+    client.set_protocol_fee(&250);
+    assert_eq!(client.get_protocol_fee(), 250);
+
+    let user = Address::generate(&env);
+    token_admin_client.mint(&user, &1000);
+    client.deposit(&token_id, &user, &1000);
+    client.lock(&token_id, &user, &1000);
+
+    // 2.5% of each payment is kept for the treasury
+    client.transfer_locked(&token_id, &user, &400, &42);
+    client.transfer_locked(&token_id, &user, &600, &42);
+
+    let provider_earnings = client.get_provider_earnings(&42, &token_id);
+    assert_eq!(provider_earnings.earned, 975);
+
+    let fees = client.get_protocol_fees(&token_id);
+    assert_eq!(fees.collected, 25);
+    assert_eq!(fees.balance, 25);
+
+    client.withdraw_protocol_fees(&token_id, &20);
+    assert_eq!(token.balance(&treasury), 20);
+
+    let fees = client.get_protocol_fees(&token_id);
+    assert_eq!(fees.collected, 25);
+    assert_eq!(fees.withdrawn, 20);
+    assert_eq!(fees.balance, 5);
+}
+
+#[test]
+fn test_protocol_fee_on_small_payments() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (provider_registry_id, _) = setup_provider_registry(&env);
+    let token_admin = Address::generate(&env);
+    let (token, token_admin_client) = create_token_contract(&env, &token_admin);
+    let token_id = token.address.clone();
+
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
+    let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
+    client.set_protocol_fee(&100);
+
+    let user = Address::generate(&env);
+    token_admin_client.mint(&user, &5000);
+    client.deposit(&token_id, &user, &5000);
+    client.lock(&token_id, &user, &5000);
+
+    // Each payment owes half a unit of fee, which is not lost to rounding
+    for _ in 0..100 {
+        client.transfer_locked(&token_id, &user, &50, &42);
+    }
+
+    assert_eq!(client.get_protocol_fees(&token_id).collected, 50);
+    assert_eq!(client.get_provider_earnings(&42, &token_id).earned, 4950);
+}
+
+#[test]
+#[should_panic(expected = "Protocol fee exceeds 1000 bps")]
+fn test_protocol_fee_above_cap() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let (provider_registry_id, _) = setup_provider_registry(&env);
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
+    let client = EscrowContractClient::new(&env, &contract_id);
+
+    client.set_protocol_fee(&1_001);
+}
+
 #[test]
 fn test_update_provider_registry() {
     let env = Env::default();
     let (old_provider_registry_id, _) = setup_provider_registry(&env);
 
     // Create escrow contract
-    let contract_id = env.register(EscrowContract, (&old_provider_registry_id,));
+    let contract_id = env.register(
+        EscrowContract,
+        (&old_provider_registry_id, &Address::generate(&env)),
+    );
     let client = EscrowContractClient::new(&env, &contract_id);
 
     // Create new provider registry
@@ -338,54 +595,58 @@ fn test_update_provider_registry() {
     assert_eq!(stored_provider_registry, new_provider_registry_id);
 }
 
-// #[test]
-// fn test_withdraw_provider_earnings() {
-//     let env = Env::default();
-//     env.mock_all_auths();
+#[test]
+fn test_withdraw_provider_earnings() {
+    let env = Env::default();
+    env.mock_all_auths();
 
-//     // Setup provider registry and add a provider
-//     let (provider_registry_id, provider_registry_client) = setup_provider_registry(&env);
-//     let provider_address = Address::generate(&env);
-//     let provider_id = provider_registry_client.add_provider(&provider_address);
+    // Setup provider registry and add a provider
+    let (provider_registry_id, provider_registry_client) = setup_provider_registry(&env);
+    let provider_address = Address::generate(&env);
+    let provider_id = provider_registry_client.add_provider(&provider_address);
 
-//     // Create token contract
-//     let token_admin = Address::generate(&env);
-//     let (token, token_admin_client) = create_token_contract(&env, &token_admin);
-//     let token_id = token.address.clone();
+    // Create token contract
+    let token_admin = Address::generate(&env);
+    let (token, token_admin_client) = create_token_contract(&env, &token_admin);
+    let token_id = token.address.clone();
 
-//     // Create escrow contract
-//     let contract_id = env.register(EscrowContract, (&provider_registry_id,));
-//     let client = EscrowContractClient::new(&env, &contract_id);
+    // Create escrow contract
+    let contract_id = env.register(
+        EscrowContract,
+        (&provider_registry_id, &Address::generate(&env)),
+    );
+    let client = EscrowContractClient::new(&env, &contract_id);
+    client.set_order_book(&Address::generate(&env));
 
-//     // Setup user and add funds
-//     let user = Address::generate(&env);
-//     token_admin_client.mint(&user, &1000);
-//     client.deposit(&token_id, &user, &700);
-//     client.lock(&token_id, &user, &500);
+    // Setup user and add funds
+    let user = Address::generate(&env);
+    token_admin_client.mint(&user, &1000);
+    client.deposit(&token_id, &user, &700);
+    client.lock(&token_id, &user, &500);
 
-//     // Transfer locked tokens to provider
-//     let amount = 300;
-//     client.transfer_locked(&token_id, &user, &amount, &provider_id);
+    // Transfer locked tokens to provider
+    let amount = 300;
+    client.transfer_locked(&token_id, &user, &amount, &provider_id);
 
-//     // Check balance before withdrawal
-//     let before_provider_balance = token.balance(&provider_address);
-//     let before_contract_balance = token.balance(&contract_id);
+    // Check balance before withdrawal
+    let before_provider_balance = token.balance(&provider_address);
+    let before_contract_balance = token.balance(&contract_id);
 
-//     // Provider withdraws earnings
-//     client.withdraw_provider_earnings(&provider_address, &token_id);
+    // Provider withdraws earnings
+    client.withdraw_provider_earnings(&provider_address, &token_id);
 
-//     // Verify token transfer
-//     let after_provider_balance = token.balance(&provider_address);
-//     let after_contract_balance = token.balance(&contract_id);
+    // Verify token transfer
+    let after_provider_balance = token.balance(&provider_address);
+    let after_contract_balance = token.balance(&contract_id);
 
-//     assert_eq!(after_provider_balance - before_provider_balance, 300);
-//     assert_eq!(before_contract_balance - after_contract_balance, 300);
+    assert_eq!(after_provider_balance - before_provider_balance, 300);
+    assert_eq!(before_contract_balance - after_contract_balance, 300);
 
-//     // Verify provider earnings record is updated
-//     let updated_earnings = client.get_provider_earnings(&provider_id, &token_id);
-//     assert_eq!(updated_earnings.withdrawn, 300);
-//     assert_eq!(updated_earnings.balance, 0);
-// }
+    // Verify provider earnings record is updated
+    let updated_earnings = client.get_provider_earnings(&provider_id, &token_id);
+    assert_eq!(updated_earnings.withdrawn, 300);
+    assert_eq!(updated_earnings.balance, 0);
+}
 
 // #[test]
 // fn test_full_escrow_lifecycle() {
@@ -403,7 +664,7 @@ fn test_update_provider_registry() {
 //     let token_id = token.address.clone();
 
 //     // Create escrow contract
-//     let contract_id = env.register(EscrowContract, (&provider_registry_id,));
+//     let contract_id = env.register(EscrowContract, (&provider_registry_id, &Address::generate(&env)));
 //     let client = EscrowContractClient::new(&env, &contract_id);
 
 //     // 1. Setup user with tokens
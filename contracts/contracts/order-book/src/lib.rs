@@ -1,7 +1,24 @@
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 use core::panic;
 
-use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, String, Vec};
+use soroban_sdk::{
+    contract, contractimpl, contracttype, Address, Bytes, BytesN, Env, IntoVal, String, Symbol,
+    Val, Vec,
+};
+
+mod provider_registry {
+    soroban_sdk::contractimport!(
+        file = "../../target/wasm32-unknown-unknown/release/provider_registry.wasm"
+    );
+}
+
+mod escrow {
+    soroban_sdk::contractimport!(file = "../../target/wasm32-unknown-unknown/release/escrow.wasm");
+}
+
+use escrow::Client as EscrowContractClient;
+use provider_registry::{Capacity, Client as ProviderRegistryContractClient, ProviderStatus};
 
 #[contract]
 pub struct OrderBookContract;
@@ -9,29 +26,295 @@ pub struct OrderBookContract;
 // Orders
 #[contracttype]
 pub struct OrderRequest {
+    tenant: Address,
+    // token the order and all bids on it are priced in
+    token: Address,
     max_price: u128,
     state: OrderState,
     spec: Specification,
     number_of_blocks: u32,
+    bid_deadline: u32,
+    auction: AuctionMode,
+    filled: u64,
+    terms: OrderTerms,
+    created_at: u32,
+}
+
+// Terms of an order that carry over into the leases it is filled with.
+#[contracttype]
+#[derive(Clone)]
+pub struct OrderTerms {
+    // blocks a provider is paid for at least if the tenant terminates early
+    pub min_billing_blocks: u32,
+    pub sla: SlaTerms,
+    pub class: OrderClass,
+}
+
+impl OrderTerms {
+    // Part of a price the tenant locks up front. Forward orders only lock
+    // their deposit until the reserved window approaches.
+    fn deposit_price(&self, price: u128) -> u128 {
+        match &self.class {
+            OrderClass::Forward(forward) => price * forward.deposit_bps as u128 / 10_000,
+            _ => price,
+        }
+    }
+}
+
+// Spot leases are cheaper but their provider may preempt them for a
+// better-paying regular order that needs the capacity. Forward orders reserve
+// capacity for a window starting at a future ledger.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderClass {
+    Regular,
+    Spot,
+    Forward(ForwardTerms),
+}
+
+// Leases of a forward order start at `start_block`. Only `deposit_bps` of the
+// price is locked when the lease is matched, the rest can be locked by anyone
+// from `funding_blocks` ledgers before the start. A lease that is not fully
+// funded by the start lapses and its deposit goes to the provider.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForwardTerms {
+    pub start_block: u32,
+    pub deposit_bps: u32,
+    pub funding_blocks: u32,
+}
+
+// Service level the tenant expects from the provider. Zero uptime means no
+// SLA.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlaTerms {
+    pub min_uptime_bps: u32,
+    pub max_latency_ms: u32,
+    // ledgers of downtime allowed before penalties apply
+    pub downtime_budget: u32,
+    // in ascending order of downtime
+    pub penalties: Vec<SlaPenalty>,
+}
+
+// Share of the lease price refunded to the tenant once downtime exceeds the
+// budget by `downtime_blocks`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlaPenalty {
+    pub downtime_blocks: u32,
+    pub refund_bps: u32,
+}
+
+impl SlaTerms {
+    fn validate(&self) {
+        if self.min_uptime_bps > 10_000 {
+            panic!("Minimum uptime exceeds 10000 bps");
+        }
+
+        let mut previous: Option<SlaPenalty> = None;
+        for penalty in self.penalties.iter() {
+            if penalty.refund_bps > 10_000 {
+                panic!("Penalty refund exceeds 10000 bps");
+            }
+
+            if let Some(previous) = previous {
+                if penalty.downtime_blocks <= previous.downtime_blocks
+                    || penalty.refund_bps < previous.refund_bps
+                {
+                    panic!("Penalties must escalate with downtime");
+                }
+            }
+            previous = Some(penalty);
+        }
+    }
 }
 
 #[contracttype]
+#[derive(Clone)]
 struct Specification {
-    spec: String,
+    spec: OrderSpec,
     trust_levels: Vec<TrustLevel>,
     quantity: u64,
     max_price: u128,
 }
 
+// Tenants can describe what they order as free-form text or as typed
+// resources that providers can filter on.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrderSpec {
+    Text(String),
+    Resources(ResourceSpec),
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourceSpec {
+    pub cpu_millis: u32,
+    pub memory_mb: u64,
+    pub storage_gb: u64,
+    pub gpus: u32,
+    pub region: String,
+    pub image_digest: BytesN<32>,
+    pub ports: Vec<u32>,
+    pub capabilities: Vec<Symbol>,
+}
+
+impl ResourceSpec {
+    fn validate(&self) {
+        if self.cpu_millis == 0 || self.memory_mb == 0 || self.storage_gb == 0 {
+            panic!("Resource quantities must be positive");
+        }
+
+        for port in self.ports.iter() {
+            if port == 0 || port > 65535 {
+                panic!("Invalid port {}", port);
+            }
+        }
+    }
+}
+
+impl OrderSpec {
+    // Resources `quantity` units of the spec occupy on a provider. Free-form
+    // specs are not accounted.
+    fn capacity(&self, quantity: u64) -> Capacity {
+        match self {
+            OrderSpec::Resources(resources) => Capacity {
+                cpu_millis: resources.cpu_millis as u64 * quantity,
+                memory_mb: resources.memory_mb * quantity,
+                storage_gb: resources.storage_gb * quantity,
+                gpus: resources.gpus as u64 * quantity,
+            },
+            OrderSpec::Text(_) => Capacity::zero(),
+        }
+    }
+}
+
+// Arithmetic on the registry's capacity type for committed capacity
+// accounting.
+impl Capacity {
+    fn zero() -> Self {
+        Capacity {
+            cpu_millis: 0,
+            memory_mb: 0,
+            storage_gb: 0,
+            gpus: 0,
+        }
+    }
+
+    fn plus(&self, other: &Capacity) -> Capacity {
+        Capacity {
+            cpu_millis: self.cpu_millis + other.cpu_millis,
+            memory_mb: self.memory_mb + other.memory_mb,
+            storage_gb: self.storage_gb + other.storage_gb,
+            gpus: self.gpus + other.gpus,
+        }
+    }
+
+    fn saturating_sub(&self, other: &Capacity) -> Capacity {
+        Capacity {
+            cpu_millis: self.cpu_millis.saturating_sub(other.cpu_millis),
+            memory_mb: self.memory_mb.saturating_sub(other.memory_mb),
+            storage_gb: self.storage_gb.saturating_sub(other.storage_gb),
+            gpus: self.gpus.saturating_sub(other.gpus),
+        }
+    }
+
+    fn fits_within(&self, other: &Capacity) -> bool {
+        self.cpu_millis <= other.cpu_millis
+            && self.memory_mb <= other.memory_mb
+            && self.storage_gb <= other.storage_gb
+            && self.gpus <= other.gpus
+    }
+}
+
+// Open orders take priced bids until the bid deadline. Sealed orders take
+// commitments until the bid deadline and reveals until the given ledger.
+// Dutch orders are taken by the first eligible provider at a price that falls
+// over time.
+#[contracttype]
+#[derive(Clone)]
+pub enum AuctionMode {
+    Open,
+    Sealed(u32),
+    Dutch(DutchAuction),
+}
+
+// The price falls linearly from the order's max price to the floor price over
+// `decay_blocks` ledgers from the order's creation, then stays at the floor.
+#[contracttype]
+#[derive(Clone)]
+pub struct DutchAuction {
+    pub floor_price: u128,
+    pub decay_blocks: u32,
+}
+
 #[contracttype]
+#[derive(Clone, PartialEq)]
 pub enum OrderState {
     Active,
     Closed,
     Complete,
 }
 
+// Empty states match every state and unset fields match every order.
+#[contracttype]
+pub struct OrderFilter {
+    pub states: Vec<OrderState>,
+    pub tenant: Option<Address>,
+    pub token: Option<Address>,
+    pub min_price: Option<u128>,
+    pub max_price: Option<u128>,
+}
+
+// A stored order a tenant can post again and again. Each instance is an open
+// auction taking bids for `bid_window` ledgers. With a recurrence interval
+// anyone can post the next instance once it is due, funded from the tenant's
+// escrow balance. Zero recurrence means the tenant posts it themselves.
+#[contracttype]
+pub struct OrderTemplate {
+    tenant: Address,
+    token: Address,
+    max_price: u128,
+    number_of_blocks: u32,
+    quantity: u64,
+    spec: OrderSpec,
+    trust_levels: Vec<TrustLevel>,
+    bid_window: u32,
+    terms: OrderTerms,
+    recurrence: u32,
+    // ledger from which the next recurring instance can be posted
+    next_run: u32,
+    last_order: Option<u64>,
+}
+
+impl OrderTemplate {
+    fn to_order(&self, env: &Env) -> OrderRequest {
+        let now = env.ledger().sequence();
+        OrderRequest {
+            tenant: self.tenant.clone(),
+            token: self.token.clone(),
+            max_price: self.max_price,
+            state: OrderState::Active,
+            spec: Specification {
+                spec: self.spec.clone(),
+                trust_levels: self.trust_levels.clone(),
+                quantity: self.quantity,
+                max_price: self.max_price,
+            },
+            number_of_blocks: self.number_of_blocks,
+            bid_deadline: now + self.bid_window,
+            auction: AuctionMode::Open,
+            filled: 0,
+            terms: self.terms.clone(),
+            created_at: now,
+        }
+    }
+}
+
 #[contracttype]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TrustLevel {
     One,
     Two,
@@ -40,20 +323,49 @@ pub enum TrustLevel {
     Five,
 }
 
+impl From<provider_registry::TrustLevel> for TrustLevel {
+    fn from(trust_level: provider_registry::TrustLevel) -> Self {
+        match trust_level {
+            provider_registry::TrustLevel::One => TrustLevel::One,
+            provider_registry::TrustLevel::Two => TrustLevel::Two,
+            provider_registry::TrustLevel::Three => TrustLevel::Three,
+            provider_registry::TrustLevel::Four => TrustLevel::Four,
+            provider_registry::TrustLevel::Five => TrustLevel::Five,
+        }
+    }
+}
+
 // Bids
 #[contracttype]
-enum BidState {
+#[derive(PartialEq)]
+pub enum BidState {
     Active,
     Canceled,
     Matched,
+    Committed,
+    Void,
 }
 
 #[contracttype]
-struct ProviderBid {
+pub struct ProviderBid {
     order_id: u64,
     provider: u64,
     bid_price: u128,
+    quantity: u64,
     state: BidState,
+    placed_at: u32,
+    commitment: Option<BytesN<32>>,
+    // amount the provider has locked in escrow behind the bid
+    bond: u128,
+}
+
+// Providers lock `percentage` of a bid's value as a bond while it is open. A
+// matched provider that does not activate the lease within
+// `activation_blocks` forfeits the bond to the tenant.
+#[contracttype]
+pub struct BidBond {
+    pub percentage: u32,
+    pub activation_blocks: u32,
 }
 
 // storage
@@ -63,51 +375,224 @@ pub enum DataKey {
     OrderCount,
     BidCount,
     LeaseCount,
+    ProviderRegistry,
+    Escrow,
+    Admin,
+    AcceptedTokens,
+    ActiveOrders,
+    BidBond,
+    Arbiter,
+    DisputeWindow,
+    TemplateCount,
 }
 
 #[contracttype]
 pub enum OrderRequests {
     Id(u64),
     ProviderOrder(u64),
+    Template(u64),
 }
 
 #[contracttype]
 pub enum BidStates {
     Bids(u64),
-    ProviderBids(Address),
+    // bid indexes grow without bound, so they are kept in persistent storage
+    ProviderBids(u64),
+    OrderBids(u64),
+    // as are the lease records and their indexes
     Leases(u64),
+    TenantLeases(Address),
+    ProviderLeases(u64),
+    OrderLeases(u64),
+    ProviderCapacity(u64),
+    ProviderSpotCapacity(u64),
+    Preemptions(u64),
 }
 
 // Lease
 #[contracttype]
 pub struct Lease {
     order_id: u64,
+    bid_id: u64,
     provider_id: u64,
+    tenant: Address,
+    token: Address,
+    price: u128,
+    claimed: u128,
     start_block: u32,
     end_block: u32,
     state: LeaseState,
     container: Option<String>,
+    auto_renew: AutoRenew,
+    // fixed at match time from the order's terms
+    sla: SlaTerms,
+    // ledgers after the end block the tenant can dispute the final payout
+    dispute_window: u32,
+    evidence: Option<BytesN<32>>,
+    // replacement order opened when the lease was migrated away
+    successor_order: Option<u64>,
+    // deployment the tenant asks for, with secrets encrypted to
+    // `secrets_key`, the provider's registered encryption key
+    manifest_hash: Option<BytesN<32>>,
+    secrets_uri: Option<String>,
+    secrets_key: Option<BytesN<32>>,
+    // manifest the provider reported deploying at activation
+    deployed_manifest: Option<BytesN<32>>,
+    class: OrderClass,
+    // part of the price the tenant has yet to lock
+    unfunded: u128,
+    // part of a forward lease's price the tenant forfeits by backing out
+    deposit: u128,
+}
+
+// Extends a lease by the given number of blocks when it ends, as long as
+// renewals remain and the tenant can fund them. No renewals left means
+// auto-renewal is off.
+#[contracttype]
+pub struct AutoRenew {
+    extra_blocks: u32,
+    renewals_left: u32,
 }
 
+// Leases start in Initiate and hold the tenant's funds locked in escrow until
+// they are Completed (paid to the provider) or Canceled (refunded). An Active
+// lease that ran its term is Ended while its final payout waits out the
+// dispute window, and Disputed until the arbiter resolves it.
 #[contracttype]
+#[derive(PartialEq)]
 pub enum LeaseState {
     Initiate,
     Active,
     Canceled,
     Completed,
+    Ended,
+    Disputed,
+}
+
+#[contracttype]
+pub enum LeaseOwner {
+    Tenant(Address),
+    Provider(u64),
 }
 
 #[contractimpl]
 impl OrderBookContract {
+    pub fn __constructor(env: Env, provider_registry: Address, escrow: Address, admin: Address) {
+        env.storage()
+            .instance()
+            .set(&DataKey::ProviderRegistry, &provider_registry);
+        env.storage().instance().set(&DataKey::Escrow, &escrow);
+        env.storage().instance().set(&DataKey::Admin, &admin);
+    }
+
+    // Allows orders to be priced in the given token.
+    pub fn add_accepted_token(env: Env, token: Address) {
+        OrderBookContract::get_admin(env.clone()).require_auth();
+
+        let mut tokens = OrderBookContract::get_accepted_tokens(env.clone());
+        if !tokens.contains(&token) {
+            tokens.push_back(token);
+            env.storage()
+                .instance()
+                .set(&DataKey::AcceptedTokens, &tokens);
+        }
+    }
+
+    // Stops new orders from using the token. Existing orders keep settling in
+    // it.
+    pub fn remove_accepted_token(env: Env, token: Address) {
+        OrderBookContract::get_admin(env.clone()).require_auth();
+
+        let mut tokens = OrderBookContract::get_accepted_tokens(env.clone());
+        if let Some(index) = tokens.first_index_of(&token) {
+            tokens.remove(index);
+            env.storage()
+                .instance()
+                .set(&DataKey::AcceptedTokens, &tokens);
+        }
+    }
+
+    pub fn set_bid_bond(env: Env, percentage: u32, activation_blocks: u32) {
+        OrderBookContract::get_admin(env.clone()).require_auth();
+
+        if percentage > 100 {
+            panic!("Bond percentage can not exceed 100");
+        }
+
+        env.storage().instance().set(
+            &DataKey::BidBond,
+            &BidBond {
+                percentage,
+                activation_blocks,
+            },
+        );
+    }
+
+    pub fn set_arbiter(env: Env, arbiter: Address) {
+        OrderBookContract::get_admin(env.clone()).require_auth();
+
+        env.storage().instance().set(&DataKey::Arbiter, &arbiter);
+    }
+
+    // Sets how many ledgers after a lease ends its tenant can dispute the final
+    // payout. Applies to leases matched from now on.
+    pub fn set_dispute_window(env: Env, blocks: u32) {
+        OrderBookContract::get_admin(env.clone()).require_auth();
+
+        env.storage()
+            .instance()
+            .set(&DataKey::DisputeWindow, &blocks);
+    }
+
+    pub fn get_arbiter(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Arbiter)
+            .unwrap_or_else(|| panic!("Arbiter is not found"))
+    }
+
+    pub fn get_dispute_window(env: Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::DisputeWindow)
+            .unwrap_or(0)
+    }
+
+    pub fn get_bid_bond(env: Env) -> BidBond {
+        env.storage()
+            .instance()
+            .get(&DataKey::BidBond)
+            .unwrap_or(BidBond {
+                percentage: 0,
+                activation_blocks: 0,
+            })
+    }
+
+    pub fn get_accepted_tokens(env: Env) -> Vec<Address> {
+        env.storage()
+            .instance()
+            .get(&DataKey::AcceptedTokens)
+            .unwrap_or(Vec::new(&env))
+    }
+
     pub fn create_order(
         env: Env,
+        tenant: Address,
+        token: Address,
         max_price: u128,
         number_of_blocks: u32,
         quantity: u64,
-        spec: String,
+        spec: OrderSpec,
         trust_levels: Vec<TrustLevel>,
+        bid_deadline: u32,
+        auction: AuctionMode,
+        terms: OrderTerms,
     ) -> u64 {
+        tenant.require_auth();
+
         let order_request = OrderRequest {
+            tenant,
+            token,
             max_price,
             state: OrderState::Active,
             spec: Specification {
@@ -117,8 +602,195 @@ impl OrderBookContract {
                 max_price,
             },
             number_of_blocks,
+            bid_deadline,
+            auction,
+            filled: 0,
+            terms,
+            created_at: env.ledger().sequence(),
+        };
+
+        OrderBookContract::validate_order(&env, &order_request);
+
+        // lock the maximum the tenant can be charged for the order
+        OrderBookContract::escrow_client(&env).lock(
+            &order_request.token,
+            &order_request.tenant,
+            &OrderBookContract::to_escrow_amount(
+                order_request.terms.deposit_price(order_request.max_price)
+                    * order_request.spec.quantity as u128,
+            ),
+        );
+
+        OrderBookContract::store_new_order(env, order_request)
+    }
+
+    fn validate_order(env: &Env, order: &OrderRequest) {
+        if !OrderBookContract::get_accepted_tokens(env.clone()).contains(&order.token) {
+            panic!("Payment token is not accepted");
+        }
+
+        if order.bid_deadline <= env.ledger().sequence() {
+            panic!("Bid deadline must be in the future");
+        }
+
+        if order.number_of_blocks == 0 {
+            panic!("Number of blocks must be positive");
+        }
+
+        if let OrderSpec::Resources(resources) = &order.spec.spec {
+            resources.validate();
+        }
+
+        order.terms.sla.validate();
+
+        if let OrderClass::Forward(forward) = &order.terms.class {
+            if forward.start_block <= order.bid_deadline {
+                panic!("Forward start must be after the bid deadline");
+            }
+
+            if forward.deposit_bps > 10_000 {
+                panic!("Deposit exceeds 10000 bps");
+            }
+        }
+
+        match &order.auction {
+            AuctionMode::Sealed(reveal_deadline) if *reveal_deadline <= order.bid_deadline => {
+                panic!("Reveal deadline must be after the bid deadline")
+            }
+            AuctionMode::Dutch(dutch) if dutch.floor_price > order.max_price => {
+                panic!("Floor price exceeds max price")
+            }
+            AuctionMode::Dutch(dutch) if dutch.decay_blocks == 0 => {
+                panic!("Price decay must last at least one block")
+            }
+            _ => {}
+        }
+    }
+
+    // Stores an order template for the tenant and returns its ID. No funds are
+    // locked until an order is created from it. Forward orders reserve a fixed
+    // start block, so they can not be templated.
+    pub fn create_template(
+        env: Env,
+        tenant: Address,
+        token: Address,
+        max_price: u128,
+        number_of_blocks: u32,
+        quantity: u64,
+        spec: OrderSpec,
+        trust_levels: Vec<TrustLevel>,
+        bid_window: u32,
+        terms: OrderTerms,
+        recurrence: u32,
+    ) -> u64 {
+        tenant.require_auth();
+
+        if let OrderClass::Forward(_) = terms.class {
+            panic!("Forward orders can not be templated");
+        }
+
+        let template = OrderTemplate {
+            tenant,
+            token,
+            max_price,
+            number_of_blocks,
+            quantity,
+            spec,
+            trust_levels,
+            bid_window,
+            terms,
+            recurrence,
+            next_run: env.ledger().sequence(),
+            last_order: None,
         };
+        OrderBookContract::validate_order(&env, &template.to_order(&env));
+
+        let template_id = env
+            .storage()
+            .instance()
+            .get(&DataKey::TemplateCount)
+            .unwrap_or(0u64)
+            + 1;
+        env.storage()
+            .instance()
+            .set(&DataKey::TemplateCount, &template_id);
+        env.storage()
+            .instance()
+            .set(&OrderRequests::Template(template_id), &template);
+
+        template_id
+    }
+
+    pub fn get_template(env: Env, template_id: u64) -> OrderTemplate {
+        env.storage()
+            .instance()
+            .get(&OrderRequests::Template(template_id))
+            .unwrap_or_else(|| panic!("Template with ID {} not found", template_id))
+    }
+
+    pub fn remove_template(env: Env, template_id: u64) {
+        let template = OrderBookContract::get_template(env.clone(), template_id);
+        template.tenant.require_auth();
+
+        env.storage()
+            .instance()
+            .remove(&OrderRequests::Template(template_id));
+    }
+
+    // Posts an order from the tenant's template.
+    pub fn create_order_from_template(env: Env, template_id: u64) -> u64 {
+        let template = OrderBookContract::get_template(env.clone(), template_id);
+        template.tenant.require_auth();
+
+        OrderBookContract::instantiate_template(env, template_id, template)
+    }
+
+    // Posts the next instance of a recurring template once it is due. Anyone
+    // can call this; it fails if the tenant's escrow balance can not cover the
+    // order.
+    pub fn run_template(env: Env, template_id: u64) -> u64 {
+        let template = OrderBookContract::get_template(env.clone(), template_id);
 
+        if template.recurrence == 0 {
+            panic!("Template with ID {} does not recur", template_id);
+        }
+
+        if env.ledger().sequence() < template.next_run {
+            panic!(
+                "Template with ID {} is not due until {}",
+                template_id, template.next_run
+            );
+        }
+
+        OrderBookContract::instantiate_template(env, template_id, template)
+    }
+
+    fn instantiate_template(env: Env, template_id: u64, mut template: OrderTemplate) -> u64 {
+        let order_request = template.to_order(&env);
+        OrderBookContract::validate_order(&env, &order_request);
+
+        // the tenant agreed to fund instances when storing the template
+        OrderBookContract::escrow_client(&env).lock_from_order_book(
+            &order_request.token,
+            &order_request.tenant,
+            &OrderBookContract::to_escrow_amount(
+                order_request.terms.deposit_price(order_request.max_price)
+                    * order_request.spec.quantity as u128,
+            ),
+        );
+
+        let order_id = OrderBookContract::store_new_order(env.clone(), order_request);
+
+        template.next_run = env.ledger().sequence() + template.recurrence;
+        template.last_order = Some(order_id);
+        env.storage()
+            .instance()
+            .set(&OrderRequests::Template(template_id), &template);
+
+        order_id
+    }
+
+    fn store_new_order(env: Env, order_request: OrderRequest) -> u64 {
         let mut order_count = env
             .storage()
             .instance()
@@ -137,23 +809,58 @@ impl OrderBookContract {
             .instance()
             .set(&DataKey::OrderCount, &order_count);
 
+        env.storage()
+            .instance()
+            .update(&DataKey::ActiveOrders, |opt_orders: Option<Vec<u64>>| {
+                let mut orders = opt_orders.unwrap_or(Vec::new(&env));
+                orders.push_back(order_count);
+                orders
+            });
+
+        OrderBookContract::publish_order_event(
+            &env,
+            "order_created",
+            order_count,
+            &order_request.tenant,
+            (
+                order_request.max_price,
+                order_request.spec.quantity,
+                order_request.bid_deadline,
+            ),
+        );
+
         order_count
     }
 
-    fn update_order_to_complete(
-        env: Env,
-        order_id: u64,
-        provider_id: u64,
-        amount: u128,
-    ) -> OrderRequest {
+    // Records a fill against the order and completes it once its quantity is
+    // reached.
+    fn fill_order(env: Env, order_id: u64, provider_id: u64, quantity: u64) -> OrderRequest {
         let mut order: OrderRequest = env
             .storage()
             .instance()
             .get(&OrderRequests::Id(order_id))
             .unwrap_or_else(|| panic!("Order with ID {} not found", order_id));
 
-        order.state = OrderState::Complete;
-        order.max_price = amount;
+        if quantity > order.spec.quantity - order.filled {
+            panic!(
+                "Bid quantity exceeds remaining quantity of order {}",
+                order_id
+            );
+        }
+
+        order.filled += quantity;
+        if order.filled == order.spec.quantity {
+            order.state = OrderState::Complete;
+            OrderBookContract::remove_active_order(env.clone(), order_id);
+            OrderBookContract::release_losing_bonds(env.clone(), order_id, &order.token);
+            OrderBookContract::publish_order_event(
+                &env,
+                "order_completed",
+                order_id,
+                &order.tenant,
+                order.filled,
+            );
+        }
 
         env.storage().instance().update(
             &OrderRequests::ProviderOrder(provider_id),
@@ -171,134 +878,1989 @@ impl OrderBookContract {
     }
 
     pub fn update_order_to_closed(env: Env, order_id: u64) {
-        let mut order: OrderRequest = env
-            .storage()
-            .instance()
-            .get(&OrderRequests::Id(order_id))
-            .unwrap_or_else(|| panic!("Order with ID {} not found", order_id));
+        let order = OrderBookContract::get_order(env.clone(), order_id);
+        order.tenant.require_auth();
+
+        OrderBookContract::close_order(env, order_id);
+    }
+
+    // Closes an active order and releases the funds locked for its unfilled
+    // quantity.
+    fn close_order(env: Env, order_id: u64) {
+        let mut order = OrderBookContract::get_order(env.clone(), order_id);
+
+        if order.state != OrderState::Active {
+            panic!("Order with ID {} is not active", order_id);
+        }
 
         order.state = OrderState::Closed;
+        OrderBookContract::remove_active_order(env.clone(), order_id);
+        OrderBookContract::release_losing_bonds(env.clone(), order_id, &order.token);
+        OrderBookContract::publish_order_event(
+            &env,
+            "order_closed",
+            order_id,
+            &order.tenant,
+            order.filled,
+        );
+
+        let unfilled = order.spec.quantity - order.filled;
+        if unfilled > 0 {
+            OrderBookContract::escrow_client(&env).unlock_tokens(
+                &order.token,
+                &order.tenant,
+                &OrderBookContract::to_escrow_amount(
+                    order.terms.deposit_price(order.max_price) * unfilled as u128,
+                ),
+            );
+        }
 
         env.storage()
             .instance()
             .set(&OrderRequests::Id(order_id), &order);
     }
 
-    pub fn get_order(env: Env, order_id: u64) -> OrderRequest {
-        env.storage()
-            .instance()
-            .get(&OrderRequests::Id(order_id))
-            .unwrap_or_else(|| panic!("Order with ID {} not found", order_id))
-    }
+    // Amends an order while bidding is open. The max price and bid deadline can
+    // only be raised and the allowed trust levels only widened, so bids already
+    // placed stay valid. Raising the max price locks the difference for the
+    // unfilled quantity.
+    pub fn amend_order(
+        env: Env,
+        order_id: u64,
+        max_price: u128,
+        bid_deadline: u32,
+        trust_levels: Vec<TrustLevel>,
+    ) {
+        let mut order = OrderBookContract::get_order(env.clone(), order_id);
+        order.tenant.require_auth();
 
-    pub fn list_orders(env: Env) -> Vec<OrderRequest> {
-        let mut orders = Vec::new(&env);
-        let order_count: u64 = env
-            .storage()
+        if order.state != OrderState::Active || env.ledger().sequence() > order.bid_deadline {
+            panic!("Order with ID {} is not open for bidding", order_id);
+        }
+
+        if max_price < order.max_price {
+            panic!("Max price can not be lowered");
+        }
+
+        if bid_deadline < order.bid_deadline {
+            panic!("Bid deadline can not be moved earlier");
+        }
+
+        if let AuctionMode::Sealed(reveal_deadline) = order.auction {
+            if reveal_deadline <= bid_deadline {
+                panic!("Reveal deadline must be after the bid deadline");
+            }
+        }
+
+        if let OrderClass::Forward(forward) = &order.terms.class {
+            if forward.start_block <= bid_deadline {
+                panic!("Forward start must be after the bid deadline");
+            }
+        }
+
+        for trust_level in order.spec.trust_levels.iter() {
+            if !trust_levels.contains(&trust_level) {
+                panic!("Allowed trust levels can only be widened");
+            }
+        }
+
+        let unfilled = order.spec.quantity - order.filled;
+        let increase = (order.terms.deposit_price(max_price)
+            - order.terms.deposit_price(order.max_price))
+            * unfilled as u128;
+        if increase > 0 {
+            OrderBookContract::escrow_client(&env).lock(
+                &order.token,
+                &order.tenant,
+                &OrderBookContract::to_escrow_amount(increase),
+            );
+        }
+
+        order.max_price = max_price;
+        order.spec.max_price = max_price;
+        order.bid_deadline = bid_deadline;
+        order.spec.trust_levels = trust_levels;
+
+        env.storage()
             .instance()
-            .get(&DataKey::OrderCount)
-            .unwrap_or(0);
+            .set(&OrderRequests::Id(order_id), &order);
+        OrderBookContract::publish_order_event(
+            &env,
+            "order_amended",
+            order_id,
+            &order.tenant,
+            (max_price, bid_deadline, order.spec.trust_levels.clone()),
+        );
+    }
+
+    pub fn get_order(env: Env, order_id: u64) -> OrderRequest {
+        env.storage()
+            .instance()
+            .get(&OrderRequests::Id(order_id))
+            .unwrap_or_else(|| panic!("Order with ID {} not found", order_id))
+    }
+
+    // Lists up to `limit` orders matching the filter with IDs greater than
+    // `cursor`, in ID order. Pass the last returned ID as the next cursor.
+    pub fn list_orders(
+        env: Env,
+        filter: OrderFilter,
+        cursor: u64,
+        limit: u32,
+    ) -> Vec<(u64, OrderRequest)> {
+        let mut orders = Vec::new(&env);
+
+        // returns false once the page is full
+        let mut visit = |order_id: u64| -> bool {
+            if orders.len() >= limit {
+                return false;
+            }
+
+            if let Some(order) = env
+                .storage()
+                .instance()
+                .get::<_, OrderRequest>(&OrderRequests::Id(order_id))
+            {
+                if OrderBookContract::order_matches(&filter, &order) {
+                    orders.push_back((order_id, order));
+                }
+            }
+
+            true
+        };
+
+        // only active orders are requested, walk the active index
+        if filter.states.len() == 1 && filter.states.get_unchecked(0) == OrderState::Active {
+            let active_orders: Vec<u64> = env
+                .storage()
+                .instance()
+                .get(&DataKey::ActiveOrders)
+                .unwrap_or(Vec::new(&env));
 
-        for i in 1..=order_count {
-            if let Some(order) = env.storage().instance().get(&OrderRequests::Id(i)) {
-                orders.push_back(order);
+            for order_id in active_orders.iter() {
+                if order_id > cursor && !visit(order_id) {
+                    break;
+                }
+            }
+        } else {
+            let order_count: u64 = env
+                .storage()
+                .instance()
+                .get(&DataKey::OrderCount)
+                .unwrap_or(0);
+
+            for order_id in (cursor + 1)..=order_count {
+                if !visit(order_id) {
+                    break;
+                }
             }
         }
 
         orders
     }
 
-    pub fn place_bid(env: Env, order_id: u64, provider: u64, bid_price: u128) -> u64 {
-        let mut bid_count = env
+    pub fn place_bid(
+        env: Env,
+        order_id: u64,
+        provider: u64,
+        bid_price: u128,
+        quantity: u64,
+    ) -> u64 {
+        let order: OrderRequest = env
             .storage()
             .instance()
-            .get(&DataKey::BidCount)
-            .unwrap_or(0);
+            .get(&OrderRequests::Id(order_id))
+            .unwrap_or_else(|| panic!("Order with ID {} not found", order_id));
+
+        if order.state != OrderState::Active || env.ledger().sequence() > order.bid_deadline {
+            panic!("Order with ID {} is not open for bidding", order_id);
+        }
+
+        match order.auction {
+            AuctionMode::Sealed(_) => {
+                panic!("Order with ID {} only accepts sealed bids", order_id)
+            }
+            AuctionMode::Dutch(_) => panic!("Order with ID {} can only be taken", order_id),
+            AuctionMode::Open => {}
+        }
+
+        if order.max_price < bid_price {
+            panic!("Bid price exceeds max price");
+        }
+
+        OrderBookContract::check_bid_quantity(&order, order_id, quantity);
+        OrderBookContract::check_capacity(
+            env.clone(),
+            &order,
+            order_id,
+            provider,
+            quantity,
+            order.terms.class == OrderClass::Regular,
+        );
+
+        OrderBookContract::require_provider_auth(env.clone(), provider);
+
+        let bond = OrderBookContract::lock_bond(
+            env.clone(),
+            &order.token,
+            provider,
+            bid_price * quantity as u128,
+        );
+
+        let bid = ProviderBid {
+            order_id,
+            provider,
+            bid_price,
+            quantity,
+            state: BidState::Active,
+            placed_at: env.ledger().sequence(),
+            commitment: None,
+            bond,
+        };
+
+        OrderBookContract::store_new_bid(env, bid)
+    }
 
+    // Commits to a sealed bid. The commitment is the sha256 of the big-endian
+    // bid price followed by a 32 byte salt.
+    pub fn commit_bid(
+        env: Env,
+        order_id: u64,
+        provider: u64,
+        quantity: u64,
+        commitment: BytesN<32>,
+    ) -> u64 {
         let order: OrderRequest = env
             .storage()
             .instance()
             .get(&OrderRequests::Id(order_id))
             .unwrap_or_else(|| panic!("Order with ID {} not found", order_id));
 
-        if order.max_price < bid_price {
-            panic!("Bid price exceeds max price");
+        if order.state != OrderState::Active || env.ledger().sequence() > order.bid_deadline {
+            panic!("Order with ID {} is not open for bidding", order_id);
         }
 
-        bid_count += 1;
+        if !matches!(order.auction, AuctionMode::Sealed(_)) {
+            panic!("Order with ID {} does not accept sealed bids", order_id);
+        }
+
+        OrderBookContract::check_bid_quantity(&order, order_id, quantity);
+        OrderBookContract::check_capacity(
+            env.clone(),
+            &order,
+            order_id,
+            provider,
+            quantity,
+            order.terms.class == OrderClass::Regular,
+        );
+
+        OrderBookContract::require_provider_auth(env.clone(), provider);
+
+        // the price is hidden, so the bond covers the order's max price
+        let bond = OrderBookContract::lock_bond(
+            env.clone(),
+            &order.token,
+            provider,
+            order.max_price * quantity as u128,
+        );
+
+        let bid = ProviderBid {
+            order_id,
+            provider,
+            bid_price: 0,
+            quantity,
+            state: BidState::Committed,
+            placed_at: env.ledger().sequence(),
+            commitment: Some(commitment),
+            bond,
+        };
+
+        OrderBookContract::store_new_bid(env, bid)
+    }
+
+    // Takes a Dutch order at its current price and opens a lease right away.
+    // Only providers the order's trust levels allow may take it.
+    pub fn take_order(env: Env, order_id: u64, provider: u64, quantity: u64) -> u64 {
+        let order = OrderBookContract::get_order(env.clone(), order_id);
+
+        if order.state != OrderState::Active || env.ledger().sequence() > order.bid_deadline {
+            panic!("Order with ID {} is not open for bidding", order_id);
+        }
+
+        if !matches!(order.auction, AuctionMode::Dutch(_)) {
+            panic!("Order with ID {} is not a Dutch auction", order_id);
+        }
+
+        OrderBookContract::check_bid_quantity(&order, order_id, quantity);
+
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        let provider_info =
+            ProviderRegistryContractClient::new(&env, &provider_registry).get_provider(&provider);
+        provider_info.address.require_auth();
+
+        if provider_info.status != ProviderStatus::Active {
+            panic!("Provider {} is not active", provider);
+        }
+
+        if !order
+            .spec
+            .trust_levels
+            .contains(TrustLevel::from(provider_info.trust_level))
+        {
+            panic!(
+                "Provider {} is not eligible for order {}",
+                provider, order_id
+            );
+        }
+
+        OrderBookContract::check_capacity(
+            env.clone(),
+            &order,
+            order_id,
+            provider,
+            quantity,
+            order.terms.class == OrderClass::Regular,
+        );
+
+        let bid_price = OrderBookContract::get_current_price(env.clone(), order_id);
+        let bond = OrderBookContract::lock_bond(
+            env.clone(),
+            &order.token,
+            provider,
+            bid_price * quantity as u128,
+        );
 
         let bid = ProviderBid {
             order_id,
             provider,
             bid_price,
+            quantity,
             state: BidState::Active,
+            placed_at: env.ledger().sequence(),
+            commitment: None,
+            bond,
         };
 
+        let bid_id = OrderBookContract::store_new_bid(env.clone(), bid);
+        OrderBookContract::match_bid(env, bid_id)
+    }
+
+    // Price a Dutch order can be taken at in the current ledger. Other orders
+    // are priced at their max price.
+    pub fn get_current_price(env: Env, order_id: u64) -> u128 {
+        let order = OrderBookContract::get_order(env.clone(), order_id);
+
+        match order.auction {
+            AuctionMode::Dutch(dutch) => {
+                let elapsed = (env.ledger().sequence() - order.created_at).min(dutch.decay_blocks);
+                order.max_price
+                    - (order.max_price - dutch.floor_price) * elapsed as u128
+                        / dutch.decay_blocks as u128
+            }
+            _ => order.max_price,
+        }
+    }
+
+    // Changes the price of an active bid on an open auction. A revised bid
+    // loses its time priority.
+    pub fn update_bid(env: Env, bid_id: u64, bid_price: u128) {
+        let mut bid = OrderBookContract::get_bid(env.clone(), bid_id);
+
+        if bid.state != BidState::Active {
+            panic!("Bid with ID {} is not active", bid_id);
+        }
+
+        OrderBookContract::require_provider_auth(env.clone(), bid.provider);
+
+        let order = OrderBookContract::get_order(env.clone(), bid.order_id);
+
+        if order.state != OrderState::Active || env.ledger().sequence() > order.bid_deadline {
+            panic!("Order with ID {} is not open for bidding", bid.order_id);
+        }
+
+        if order.max_price < bid_price {
+            panic!("Bid price exceeds max price");
+        }
+
+        // re-post the bond for the new price
+        OrderBookContract::release_bond(env.clone(), &order.token, &mut bid);
+        bid.bond = OrderBookContract::lock_bond(
+            env.clone(),
+            &order.token,
+            bid.provider,
+            bid_price * bid.quantity as u128,
+        );
+
+        bid.bid_price = bid_price;
+        bid.placed_at = env.ledger().sequence();
+
+        env.storage().instance().set(&BidStates::Bids(bid_id), &bid);
+        OrderBookContract::publish_bid_event(&env, "bid_revised", bid_id, &bid);
+    }
+
+    // Withdraws a bid that has not been matched yet.
+    pub fn cancel_bid(env: Env, bid_id: u64) {
+        let mut bid = OrderBookContract::get_bid(env.clone(), bid_id);
+
+        if bid.state != BidState::Active && bid.state != BidState::Committed {
+            panic!("Bid with ID {} can not be canceled", bid_id);
+        }
+
+        OrderBookContract::require_provider_auth(env.clone(), bid.provider);
+
+        let order = OrderBookContract::get_order(env.clone(), bid.order_id);
+        OrderBookContract::release_bond(env.clone(), &order.token, &mut bid);
+        bid.state = BidState::Canceled;
+
+        env.storage().instance().set(&BidStates::Bids(bid_id), &bid);
+        OrderBookContract::publish_bid_event(&env, "bid_canceled", bid_id, &bid);
+    }
+
+    pub fn get_bid(env: Env, bid_id: u64) -> ProviderBid {
+        env.storage()
+            .instance()
+            .get(&BidStates::Bids(bid_id))
+            .unwrap_or_else(|| panic!("Bid with ID {} not found", bid_id))
+    }
+
+    // Lists an order's bids in the order they were placed, starting at the
+    // given offset into its bids.
+    pub fn list_bids_for_order(
+        env: Env,
+        order_id: u64,
+        cursor: u32,
+        limit: u32,
+    ) -> Vec<(u64, ProviderBid)> {
+        OrderBookContract::list_bids(env, BidStates::OrderBids(order_id), cursor, limit)
+    }
+
+    // Lists a provider's bids in the order they were placed, starting at the
+    // given offset into its bids.
+    pub fn list_bids_for_provider(
+        env: Env,
+        provider_id: u64,
+        cursor: u32,
+        limit: u32,
+    ) -> Vec<(u64, ProviderBid)> {
+        OrderBookContract::list_bids(env, BidStates::ProviderBids(provider_id), cursor, limit)
+    }
+
+    fn list_bids(env: Env, key: BidStates, cursor: u32, limit: u32) -> Vec<(u64, ProviderBid)> {
+        let bid_ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or(Vec::new(&env));
+
+        let mut bids = Vec::new(&env);
+        let end = bid_ids.len().min(cursor.saturating_add(limit));
+        for i in cursor..end {
+            let bid_id = bid_ids.get_unchecked(i);
+            bids.push_back((bid_id, OrderBookContract::get_bid(env.clone(), bid_id)));
+        }
+
+        bids
+    }
+
+    fn store_new_bid(env: Env, bid: ProviderBid) -> u64 {
+        let mut bid_count = env
+            .storage()
+            .instance()
+            .get(&DataKey::BidCount)
+            .unwrap_or(0);
+
+        bid_count += 1;
+
         env.storage()
             .instance()
             .set(&BidStates::Bids(bid_count), &bid);
         env.storage().instance().set(&DataKey::BidCount, &bid_count);
 
+        // index the bid by order and provider
+        for key in [
+            BidStates::OrderBids(bid.order_id),
+            BidStates::ProviderBids(bid.provider),
+        ] {
+            env.storage()
+                .persistent()
+                .update(&key, |opt_bids: Option<Vec<u64>>| {
+                    let mut bids = opt_bids.unwrap_or(Vec::new(&env));
+                    bids.push_back(bid_count);
+                    bids
+                });
+        }
+
+        OrderBookContract::publish_bid_event(&env, "bid_placed", bid_count, &bid);
+
         bid_count
     }
 
-    pub fn accept_bid(env: Env, bid_id: u64) {
+    // Reveals a sealed bid once the commit phase is over. Returns false and
+    // voids the bid if the price and salt do not match the commitment or the
+    // price exceeds the order's max price.
+    pub fn reveal_bid(env: Env, bid_id: u64, bid_price: u128, salt: BytesN<32>) -> bool {
         let mut bid: ProviderBid = env
             .storage()
             .instance()
             .get(&BidStates::Bids(bid_id))
             .unwrap_or_else(|| panic!("Bid with ID {} not found", bid_id));
 
-        bid.state = BidState::Matched;
+        if bid.state != BidState::Committed {
+            panic!("Bid with ID {} is not committed", bid_id);
+        }
+
+        let order: OrderRequest = env
+            .storage()
+            .instance()
+            .get(&OrderRequests::Id(bid.order_id))
+            .unwrap_or_else(|| panic!("Order with ID {} not found", bid.order_id));
+
+        let reveal_deadline = match order.auction {
+            AuctionMode::Sealed(reveal_deadline) => reveal_deadline,
+            _ => panic!("Order with ID {} is not sealed", bid.order_id),
+        };
+
+        let sequence = env.ledger().sequence();
+        if sequence <= order.bid_deadline || sequence > reveal_deadline {
+            panic!("Order with ID {} is not in its reveal phase", bid.order_id);
+        }
+
+        OrderBookContract::require_provider_auth(env.clone(), bid.provider);
+
+        let mut payload = Bytes::from_array(&env, &bid_price.to_be_bytes());
+        payload.append(&salt.into());
+        let hash: BytesN<32> = env.crypto().sha256(&payload).into();
+
+        let revealed = bid.commitment == Some(hash) && bid_price <= order.max_price;
+        if !revealed {
+            OrderBookContract::void_bid(env, bid_id, &mut bid, &order.token);
+            return false;
+        }
+
+        bid.bid_price = bid_price;
+        bid.state = BidState::Active;
 
         env.storage().instance().set(&BidStates::Bids(bid_id), &bid);
+        OrderBookContract::publish_bid_event(&env, "bid_revealed", bid_id, &bid);
 
-        let order = OrderBookContract::update_order_to_complete(
-            env.clone(),
-            bid.order_id,
-            bid.provider,
-            bid.bid_price,
-        );
-        // current block number
-        let start_block = env.ledger().sequence();
-        OrderBookContract::create_lease(
-            env,
-            bid.order_id,
-            bid.provider,
-            start_block,
-            start_block + order.number_of_blocks,
-        );
+        true
     }
 
-    fn create_lease(
-        env: Env,
-        order_id: u64,
-        provider_id: u64,
-        start_block: u32,
-        end_block: u32,
-    ) -> Lease {
-        let lease = Lease {
-            order_id,
-            provider_id,
-            start_block,
-            end_block,
-            state: LeaseState::Active,
-            container: None,
-        };
-
-        let mut lease_count = env
+    pub fn accept_bid(env: Env, bid_id: u64) -> u64 {
+        let bid: ProviderBid = env
             .storage()
             .instance()
-            .get(&DataKey::OrderCount)
-            .unwrap_or(0);
+            .get(&BidStates::Bids(bid_id))
+            .unwrap_or_else(|| panic!("Bid with ID {} not found", bid_id));
+        let order = OrderBookContract::get_order(env.clone(), bid.order_id);
+        order.tenant.require_auth();
 
-        lease_count += 1;
+        OrderBookContract::match_bid(env, bid_id)
+    }
 
-        env.storage()
+    // Matches the lowest qualified bids once bidding has closed until the
+    // order's quantity is filled. Bids are ranked by price, then by the
+    // provider's trust level, then by the ledger they were placed in. Bids of
+    // providers that are not active and bids larger than the remaining
+    // quantity are skipped. Bids left unmatched are void and their bonds
+    // returned. Closes the order if its quantity is not filled, unlocking the
+    // funds for the rest.
+    pub fn settle_order(env: Env, order_id: u64) -> Vec<u64> {
+        let order: OrderRequest = env
+            .storage()
             .instance()
-            .set(&BidStates::Leases(lease_count), &lease);
+            .get(&OrderRequests::Id(order_id))
+            .unwrap_or_else(|| panic!("Order with ID {} not found", order_id));
+
+        if order.state != OrderState::Active {
+            panic!("Order with ID {} is not active", order_id);
+        }
+
+        if env.ledger().sequence() <= order.bid_deadline {
+            panic!("Bidding for order {} is still open", order_id);
+        }
+
+        if let AuctionMode::Sealed(reveal_deadline) = order.auction {
+            if env.ledger().sequence() <= reveal_deadline {
+                panic!("Reveal phase for order {} is still open", order_id);
+            }
+        }
+
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        let provider_registry_client =
+            ProviderRegistryContractClient::new(&env, &provider_registry);
+
+        let bid_ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&BidStates::OrderBids(order_id))
+            .unwrap_or(Vec::new(&env));
+
+        let mut candidates: Vec<(u128, u32, u32, u64)> = Vec::new(&env);
+        for bid_id in bid_ids.iter() {
+            let mut bid = OrderBookContract::get_bid(env.clone(), bid_id);
+
+            // commitments that were never revealed are void
+            if bid.state == BidState::Committed {
+                OrderBookContract::void_bid(env.clone(), bid_id, &mut bid, &order.token);
+                continue;
+            }
+
+            if bid.state != BidState::Active {
+                continue;
+            }
+
+            let provider = match provider_registry_client.try_get_provider(&bid.provider) {
+                Ok(Ok(provider)) => provider,
+                _ => continue,
+            };
+
+            if provider.status != ProviderStatus::Active {
+                continue;
+            }
+
+            let trust_level = TrustLevel::from(provider.trust_level);
+            if !order.spec.trust_levels.contains(&trust_level) {
+                continue;
+            }
+
+            candidates.push_back((bid.bid_price, trust_level as u32, bid.placed_at, bid_id));
+        }
+
+        let mut matched = Vec::new(&env);
+        let mut remaining = order.spec.quantity - order.filled;
+        while remaining > 0 && !candidates.is_empty() {
+            let mut best_index = 0;
+            for i in 1..candidates.len() {
+                if candidates.get_unchecked(i) < candidates.get_unchecked(best_index) {
+                    best_index = i;
+                }
+            }
+
+            let (_, _, _, bid_id) = candidates.get_unchecked(best_index);
+            candidates.remove(best_index);
+
+            let bid: ProviderBid = env
+                .storage()
+                .instance()
+                .get(&BidStates::Bids(bid_id))
+                .unwrap();
+            // skip bids that no longer fit or whose provider has since run
+            // out of capacity
+            if bid.quantity > remaining
+                || !OrderBookContract::has_capacity(
+                    env.clone(),
+                    bid.provider,
+                    &order.spec.spec.capacity(bid.quantity),
+                    &OrderBookContract::preemptible_capacity(env.clone(), bid_id, &bid, &order),
+                )
+            {
+                continue;
+            }
+
+            OrderBookContract::match_bid(env.clone(), bid_id);
+            remaining -= bid.quantity;
+            matched.push_back(bid_id);
+        }
+
+        // bids left unmatched lost the auction
+        for bid_id in bid_ids.iter() {
+            let mut bid = OrderBookContract::get_bid(env.clone(), bid_id);
+            if bid.state == BidState::Active {
+                OrderBookContract::void_bid(env.clone(), bid_id, &mut bid, &order.token);
+            }
+        }
+
+        if remaining > 0 {
+            OrderBookContract::close_order(env, order_id);
+        }
+
+        matched
+    }
+
+    // Resources the provider has committed to its Initiate and Active leases.
+    pub fn get_committed_capacity(env: Env, provider_id: u64) -> Capacity {
+        env.storage()
+            .instance()
+            .get(&BidStates::ProviderCapacity(provider_id))
+            .unwrap_or(Capacity::zero())
+    }
+
+    // Declared capacity of the provider that is not committed to leases.
+    pub fn get_free_capacity(env: Env, provider_id: u64) -> Capacity {
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        let declared = ProviderRegistryContractClient::new(&env, &provider_registry)
+            .get_capacity(&provider_id);
+
+        declared.saturating_sub(&OrderBookContract::get_committed_capacity(env, provider_id))
+    }
+
+    pub fn get_provider_registry(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::ProviderRegistry)
+            .unwrap_or_else(|| panic!("Provider registry contract is not found"))
+    }
+
+    pub fn get_escrow(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Escrow)
+            .unwrap_or_else(|| panic!("Escrow contract is not found"))
+    }
+
+    pub fn get_admin(env: Env) -> Address {
+        env.storage()
+            .instance()
+            .get(&DataKey::Admin)
+            .unwrap_or_else(|| panic!("Admin is not found"))
+    }
+
+    fn escrow_client(env: &Env) -> EscrowContractClient<'_> {
+        EscrowContractClient::new(env, &OrderBookContract::get_escrow(env.clone()))
+    }
+
+    // Cost of extending a lease at the rate of the bid it was matched with,
+    // rounded up so that short extensions of cheap leases are not free.
+    fn extension_cost(env: Env, lease: &Lease, extra_blocks: u32) -> u128 {
+        let order = OrderBookContract::get_order(env.clone(), lease.order_id);
+        let bid: ProviderBid = env
+            .storage()
+            .instance()
+            .get(&BidStates::Bids(lease.bid_id))
+            .unwrap_or_else(|| panic!("Bid with ID {} not found", lease.bid_id));
+
+        (bid.bid_price * bid.quantity as u128 * extra_blocks as u128)
+            .div_ceil(order.number_of_blocks as u128)
+    }
+
+    // Renews an ended lease from the tenant's unlocked balance. Returns false
+    // if no renewals remain or the tenant cannot fund one.
+    fn try_auto_renew(env: Env, lease: &mut Lease) -> bool {
+        if lease.auto_renew.renewals_left == 0 {
+            return false;
+        }
+
+        let extra_blocks = lease.auto_renew.extra_blocks;
+        let cost = OrderBookContract::extension_cost(env.clone(), lease, extra_blocks);
+        let token = lease.token.clone();
+        let escrow_client = OrderBookContract::escrow_client(&env);
+
+        let balance = escrow_client.get_tenant_balance(&token, &lease.tenant);
+        let amount = OrderBookContract::to_escrow_amount(cost);
+        if balance.unlocked_balance < amount {
+            return false;
+        }
+
+        escrow_client.lock_from_order_book(&token, &lease.tenant, &amount);
+
+        lease.auto_renew.renewals_left -= 1;
+        lease.price += cost;
+        lease.end_block += extra_blocks;
+
+        true
+    }
+
+    // Moves part of the lease's locked funds to the provider's earnings.
+    fn pay_provider(env: Env, lease: &mut Lease, amount: u128) {
+        if amount == 0 {
+            return;
+        }
+
+        OrderBookContract::escrow_client(&env).transfer_locked(
+            &lease.token,
+            &lease.tenant,
+            &OrderBookContract::to_escrow_amount(amount),
+            &lease.provider_id,
+        );
+
+        lease.claimed += amount;
+    }
+
+    fn to_escrow_amount(amount: u128) -> i128 {
+        i128::try_from(amount).unwrap_or_else(|_| panic!("Amount exceeds escrow limits"))
+    }
+
+    fn require_provider_auth(env: Env, provider_id: u64) {
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        let provider = ProviderRegistryContractClient::new(&env, &provider_registry)
+            .get_provider(&provider_id);
+        provider.address.require_auth();
+    }
+
+    fn provider_address(env: Env, provider_id: u64) -> Address {
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        ProviderRegistryContractClient::new(&env, &provider_registry)
+            .get_provider(&provider_id)
+            .address
+    }
+
+    // Locks the bond for a bid of the given value from the provider's escrow
+    // balance and returns its amount.
+    fn lock_bond(env: Env, token: &Address, provider_id: u64, bid_value: u128) -> u128 {
+        let percentage = OrderBookContract::get_bid_bond(env.clone()).percentage;
+        let bond = bid_value * percentage as u128 / 100;
+        if bond > 0 {
+            OrderBookContract::escrow_client(&env).lock_from_order_book(
+                token,
+                &OrderBookContract::provider_address(env.clone(), provider_id),
+                &OrderBookContract::to_escrow_amount(bond),
+            );
+        }
+        bond
+    }
+
+    fn release_bond(env: Env, token: &Address, bid: &mut ProviderBid) {
+        if bid.bond == 0 {
+            return;
+        }
+
+        OrderBookContract::escrow_client(&env).unlock_tokens(
+            token,
+            &OrderBookContract::provider_address(env.clone(), bid.provider),
+            &OrderBookContract::to_escrow_amount(bid.bond),
+        );
+        bid.bond = 0;
+    }
+
+    fn forfeit_bond(env: Env, lease: &Lease, bid: &mut ProviderBid) {
+        if bid.bond == 0 {
+            return;
+        }
+
+        OrderBookContract::escrow_client(&env).transfer_locked_to(
+            &lease.token,
+            &OrderBookContract::provider_address(env.clone(), bid.provider),
+            &OrderBookContract::to_escrow_amount(bid.bond),
+            &lease.tenant,
+        );
+        bid.bond = 0;
+    }
+
+    // Voids a bid that can no longer win and returns its bond.
+    fn void_bid(env: Env, bid_id: u64, bid: &mut ProviderBid, token: &Address) {
+        bid.state = BidState::Void;
+        OrderBookContract::release_bond(env.clone(), token, bid);
+        env.storage()
+            .instance()
+            .set(&BidStates::Bids(bid_id), &*bid);
+    }
+
+    // Returns the bonds of every bid on the order that was not matched.
+    fn release_losing_bonds(env: Env, order_id: u64, token: &Address) {
+        let bid_ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&BidStates::OrderBids(order_id))
+            .unwrap_or(Vec::new(&env));
+
+        for bid_id in bid_ids.iter() {
+            let mut bid = OrderBookContract::get_bid(env.clone(), bid_id);
+            if bid.bond > 0 && bid.state != BidState::Matched {
+                OrderBookContract::release_bond(env.clone(), token, &mut bid);
+                env.storage().instance().set(&BidStates::Bids(bid_id), &bid);
+            }
+        }
+    }
+
+    // Order events are published under (name, order ID, tenant).
+    fn publish_order_event<D: IntoVal<Env, Val>>(
+        env: &Env,
+        name: &str,
+        order_id: u64,
+        tenant: &Address,
+        data: D,
+    ) {
+        env.events()
+            .publish((Symbol::new(env, name), order_id, tenant.clone()), data);
+    }
+
+    // Bid events are published under (name, order ID, provider ID).
+    fn publish_bid_event(env: &Env, name: &str, bid_id: u64, bid: &ProviderBid) {
+        env.events().publish(
+            (Symbol::new(env, name), bid.order_id, bid.provider),
+            (bid_id, bid.bid_price, bid.quantity),
+        );
+    }
+
+    // Lease events are published under (name, order ID, provider ID, tenant).
+    fn publish_lease_event(env: &Env, name: &str, lease_id: u64, lease: &Lease) {
+        env.events().publish(
+            (
+                Symbol::new(env, name),
+                lease.order_id,
+                lease.provider_id,
+                lease.tenant.clone(),
+            ),
+            (lease_id, lease.price),
+        );
+    }
+
+    fn order_matches(filter: &OrderFilter, order: &OrderRequest) -> bool {
+        (filter.states.is_empty() || filter.states.contains(&order.state))
+            && filter
+                .tenant
+                .as_ref()
+                .is_none_or(|tenant| *tenant == order.tenant)
+            && filter
+                .token
+                .as_ref()
+                .is_none_or(|token| *token == order.token)
+            && filter
+                .min_price
+                .is_none_or(|price| order.max_price >= price)
+            && filter
+                .max_price
+                .is_none_or(|price| order.max_price <= price)
+    }
+
+    fn remove_active_order(env: Env, order_id: u64) {
+        let mut orders: Vec<u64> = env
+            .storage()
+            .instance()
+            .get(&DataKey::ActiveOrders)
+            .unwrap_or(Vec::new(&env));
+
+        if let Some(index) = orders.first_index_of(order_id) {
+            orders.remove(index);
+            env.storage()
+                .instance()
+                .set(&DataKey::ActiveOrders, &orders);
+        }
+    }
+
+    // Whether the provider can take on the required resources once the
+    // `freed` part of its committed capacity is released.
+    fn has_capacity(env: Env, provider_id: u64, required: &Capacity, freed: &Capacity) -> bool {
+        if *required == Capacity::zero() {
+            return true;
+        }
+
+        let committed = OrderBookContract::get_committed_capacity(env.clone(), provider_id)
+            .saturating_sub(freed);
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        let declared = ProviderRegistryContractClient::new(&env, &provider_registry)
+            .get_capacity(&provider_id);
+
+        committed.plus(required).fits_within(&declared)
+    }
+
+    fn check_capacity(
+        env: Env,
+        order: &OrderRequest,
+        order_id: u64,
+        provider_id: u64,
+        quantity: u64,
+        spot_is_free: bool,
+    ) {
+        // capacity held by spot leases counts as free for bids the provider can
+        // preempt them for
+        let freed = if spot_is_free {
+            env.storage()
+                .instance()
+                .get(&BidStates::ProviderSpotCapacity(provider_id))
+                .unwrap_or(Capacity::zero())
+        } else {
+            Capacity::zero()
+        };
+
+        let required = order.spec.spec.capacity(quantity);
+        if !OrderBookContract::has_capacity(env, provider_id, &required, &freed) {
+            panic!(
+                "Provider {} does not have capacity for order {}",
+                provider_id, order_id
+            );
+        }
+    }
+
+    fn update_committed_capacity(
+        env: Env,
+        provider_id: u64,
+        amount: &Capacity,
+        commit: bool,
+        class: &OrderClass,
+    ) {
+        let keys = [
+            Some(BidStates::ProviderCapacity(provider_id)),
+            (*class == OrderClass::Spot).then_some(BidStates::ProviderSpotCapacity(provider_id)),
+        ];
+
+        for key in keys.into_iter().flatten() {
+            let committed: Capacity = env
+                .storage()
+                .instance()
+                .get(&key)
+                .unwrap_or(Capacity::zero());
+            let committed = if commit {
+                committed.plus(amount)
+            } else {
+                committed.saturating_sub(amount)
+            };
+            env.storage().instance().set(&key, &committed);
+        }
+    }
+
+    // Frees the resources a lease held on its provider once it is closed.
+    fn release_capacity(env: Env, lease: &Lease) {
+        OrderBookContract::update_committed_capacity(
+            env.clone(),
+            lease.provider_id,
+            &OrderBookContract::lease_capacity(env, lease),
+            false,
+            &lease.class,
+        );
+    }
+
+    fn lease_capacity(env: Env, lease: &Lease) -> Capacity {
+        let order = OrderBookContract::get_order(env.clone(), lease.order_id);
+        let bid = OrderBookContract::get_bid(env, lease.bid_id);
+
+        order.spec.spec.capacity(bid.quantity)
+    }
+
+    fn check_bid_quantity(order: &OrderRequest, order_id: u64, quantity: u64) {
+        if quantity == 0 || quantity > order.spec.quantity - order.filled {
+            panic!("Invalid bid quantity for order {}", order_id);
+        }
+    }
+
+    fn match_bid(env: Env, bid_id: u64) -> u64 {
+        let mut bid: ProviderBid = env
+            .storage()
+            .instance()
+            .get(&BidStates::Bids(bid_id))
+            .unwrap_or_else(|| panic!("Bid with ID {} not found", bid_id));
+
+        if bid.state != BidState::Active {
+            panic!("Bid with ID {} is not active", bid_id);
+        }
+
+        let order: OrderRequest = env
+            .storage()
+            .instance()
+            .get(&OrderRequests::Id(bid.order_id))
+            .unwrap_or_else(|| panic!("Order with ID {} not found", bid.order_id));
+
+        if order.state != OrderState::Active {
+            panic!("Order with ID {} is not active", bid.order_id);
+        }
+
+        // forward leases start at the reserved window
+        let now = env.ledger().sequence();
+        let start_block = match &order.terms.class {
+            OrderClass::Forward(forward) if now >= forward.start_block => {
+                panic!("Reserved window of order {} has started", bid.order_id)
+            }
+            OrderClass::Forward(forward) => forward.start_block,
+            _ => now,
+        };
+
+        bid.state = BidState::Matched;
+
+        env.storage().instance().set(&BidStates::Bids(bid_id), &bid);
+        OrderBookContract::publish_bid_event(&env, "bid_matched", bid_id, &bid);
+
+        // release the difference between the locked max price and the bid
+        let price = bid.bid_price * bid.quantity as u128;
+        let deposit = order.terms.deposit_price(bid.bid_price) * bid.quantity as u128;
+        let refund = order.terms.deposit_price(order.max_price) * bid.quantity as u128 - deposit;
+        if refund > 0 {
+            OrderBookContract::escrow_client(&env).unlock_tokens(
+                &order.token,
+                &order.tenant,
+                &OrderBookContract::to_escrow_amount(refund),
+            );
+        }
+
+        let order =
+            OrderBookContract::fill_order(env.clone(), bid.order_id, bid.provider, bid.quantity);
+        OrderBookContract::preempt_for_bid(env.clone(), bid_id, &bid, &order);
+        OrderBookContract::check_capacity(
+            env.clone(),
+            &order,
+            bid.order_id,
+            bid.provider,
+            bid.quantity,
+            false,
+        );
+        OrderBookContract::update_committed_capacity(
+            env.clone(),
+            bid.provider,
+            &order.spec.spec.capacity(bid.quantity),
+            true,
+            &order.terms.class,
+        );
+        OrderBookContract::create_lease(
+            env,
+            bid.order_id,
+            bid_id,
+            bid.provider,
+            &order,
+            price,
+            price - deposit,
+            start_block,
+            start_block + order.number_of_blocks,
+        )
+    }
+
+    fn create_lease(
+        env: Env,
+        order_id: u64,
+        bid_id: u64,
+        provider_id: u64,
+        order: &OrderRequest,
+        price: u128,
+        unfunded: u128,
+        start_block: u32,
+        end_block: u32,
+    ) -> u64 {
+        let tenant = order.tenant.clone();
+        let lease = Lease {
+            order_id,
+            bid_id,
+            provider_id,
+            tenant: tenant.clone(),
+            token: order.token.clone(),
+            price,
+            claimed: 0,
+            start_block,
+            end_block,
+            state: LeaseState::Initiate,
+            container: None,
+            auto_renew: AutoRenew {
+                extra_blocks: 0,
+                renewals_left: 0,
+            },
+            sla: order.terms.sla.clone(),
+            dispute_window: OrderBookContract::get_dispute_window(env.clone()),
+            evidence: None,
+            successor_order: None,
+            manifest_hash: None,
+            secrets_uri: None,
+            secrets_key: None,
+            deployed_manifest: None,
+            class: order.terms.class.clone(),
+            unfunded,
+            deposit: match order.terms.class {
+                OrderClass::Forward(_) => price - unfunded,
+                _ => 0,
+            },
+        };
+
+        let mut lease_count = env
+            .storage()
+            .instance()
+            .get(&DataKey::LeaseCount)
+            .unwrap_or(0);
+
+        lease_count += 1;
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_count), &lease);
+        env.storage()
+            .instance()
+            .set(&DataKey::LeaseCount, &lease_count);
+
+        // index the lease by tenant, provider and order
+        for key in [
+            BidStates::TenantLeases(tenant),
+            BidStates::ProviderLeases(provider_id),
+            BidStates::OrderLeases(order_id),
+        ] {
+            env.storage()
+                .persistent()
+                .update(&key, |opt_leases: Option<Vec<u64>>| {
+                    let mut leases = opt_leases.unwrap_or(Vec::new(&env));
+                    leases.push_back(lease_count);
+                    leases
+                });
+        }
+
+        OrderBookContract::publish_lease_event(&env, "lease_initiated", lease_count, &lease);
+
+        lease_count
+    }
+
+    // Tells the provider what to deploy: the hash of the deployment manifest
+    // and where to fetch the secrets bundle, encrypted to the provider's
+    // registered encryption key.
+    pub fn set_deployment(env: Env, lease_id: u64, manifest_hash: BytesN<32>, secrets_uri: String) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+        lease.tenant.require_auth();
+
+        if lease.state != LeaseState::Initiate {
+            panic!("Lease with ID {} is not awaiting activation", lease_id);
+        }
+
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        let secrets_key = ProviderRegistryContractClient::new(&env, &provider_registry)
+            .get_encryption_key(&lease.provider_id);
+
+        lease.manifest_hash = Some(manifest_hash);
+        lease.secrets_uri = Some(secrets_uri);
+        lease.secrets_key = Some(secrets_key);
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+    }
+
+    // Moves a lease from Initiate to Active once the provider has deployed the
+    // container, recording the manifest it deployed. The lease term starts at
+    // activation.
+    pub fn activate_lease(env: Env, lease_id: u64, container: String, manifest_hash: BytesN<32>) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+        if lease.state != LeaseState::Initiate {
+            panic!("Lease with ID {} is not awaiting activation", lease_id);
+        }
+
+        OrderBookContract::require_provider_auth(env.clone(), lease.provider_id);
+
+        if env.ledger().sequence() < lease.start_block {
+            panic!("Lease with ID {} starts at {}", lease_id, lease.start_block);
+        }
+
+        if lease.unfunded > 0 {
+            panic!("Lease with ID {} is not fully funded", lease_id);
+        }
+
+        if lease
+            .manifest_hash
+            .as_ref()
+            .is_some_and(|expected| *expected != manifest_hash)
+        {
+            panic!("Deployed manifest does not match lease {}", lease_id);
+        }
+
+        // the provider delivered, return its bond
+        let mut bid = OrderBookContract::get_bid(env.clone(), lease.bid_id);
+        if bid.bond > 0 {
+            OrderBookContract::release_bond(env.clone(), &lease.token, &mut bid);
+            env.storage()
+                .instance()
+                .set(&BidStates::Bids(lease.bid_id), &bid);
+        }
+
+        let start_block = env.ledger().sequence();
+        lease.end_block = start_block + (lease.end_block - lease.start_block);
+        lease.start_block = start_block;
+        lease.state = LeaseState::Active;
+        lease.container = Some(container);
+        lease.deployed_manifest = Some(manifest_hash);
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+        OrderBookContract::publish_lease_event(&env, "lease_activated", lease_id, &lease);
+    }
+
+    // Completes an active lease once its end block has passed and pays the
+    // provider from the tenant's locked funds. While the dispute window is open
+    // the lease is only Ended, and calling again after the window closes pays
+    // out. Callable by anyone.
+    pub fn complete_lease(env: Env, lease_id: u64) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+        let window_open = env.ledger().sequence() <= lease.end_block + lease.dispute_window;
+
+        match lease.state {
+            LeaseState::Active => {
+                if env.ledger().sequence() <= lease.end_block {
+                    panic!("Lease with ID {} has not ended", lease_id);
+                }
+
+                if OrderBookContract::try_auto_renew(env.clone(), &mut lease) {
+                    env.storage()
+                        .persistent()
+                        .set(&BidStates::Leases(lease_id), &lease);
+                    return;
+                }
+
+                OrderBookContract::release_capacity(env.clone(), &lease);
+
+                // hold the final payout until the dispute window closes
+                if window_open {
+                    lease.state = LeaseState::Ended;
+                    env.storage()
+                        .persistent()
+                        .set(&BidStates::Leases(lease_id), &lease);
+                    OrderBookContract::publish_lease_event(&env, "lease_ended", lease_id, &lease);
+                    return;
+                }
+            }
+            LeaseState::Ended if window_open => {
+                panic!("Dispute window of lease {} is still open", lease_id)
+            }
+            LeaseState::Ended => {}
+            _ => panic!("Lease with ID {} is not active", lease_id),
+        }
+
+        lease.state = LeaseState::Completed;
+
+        let remaining = lease.price - lease.claimed;
+        OrderBookContract::pay_provider(env.clone(), &mut lease, remaining);
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+        OrderBookContract::publish_lease_event(&env, "lease_completed", lease_id, &lease);
+    }
+
+    // Pays the provider what the lease has accrued since it was last claimed.
+    // Earnings accrue linearly with each ledger between the start and end
+    // block, except for the last dispute window's worth of blocks, which is
+    // only paid out by completion. Callable by anyone until the end block.
+    pub fn claim_lease_earnings(env: Env, lease_id: u64) -> u128 {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+        if lease.state != LeaseState::Active {
+            panic!("Lease with ID {} is not active", lease_id);
+        }
+
+        if env.ledger().sequence() > lease.end_block {
+            panic!("Lease with ID {} has ended", lease_id);
+        }
+
+        let amount = OrderBookContract::get_claimable_earnings(env.clone(), lease_id);
+        OrderBookContract::pay_provider(env.clone(), &mut lease, amount);
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+
+        amount
+    }
+
+    pub fn get_claimable_earnings(env: Env, lease_id: u64) -> u128 {
+        let lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+        // hold back the final payout so a dispute has something to freeze
+        let term = lease.end_block - lease.start_block;
+        let stream_end = lease.end_block - lease.dispute_window.min(term);
+
+        OrderBookContract::earned_until(&lease, env.ledger().sequence().min(stream_end))
+    }
+
+    // What an active lease has accrued by the given block and is not yet
+    // claimed.
+    fn earned_until(lease: &Lease, block: u32) -> u128 {
+        if lease.state != LeaseState::Active {
+            return 0;
+        }
+
+        let block = block.clamp(lease.start_block, lease.end_block);
+        let accrued = lease.price * (block - lease.start_block) as u128
+            / (lease.end_block - lease.start_block) as u128;
+
+        accrued.saturating_sub(lease.claimed)
+    }
+
+    // Ends an active lease early at the tenant's request. The provider is paid
+    // for the blocks served so far, but at least the order's minimum billing
+    // period, and the rest is returned to the tenant.
+    pub fn terminate_lease(env: Env, lease_id: u64) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+        lease.tenant.require_auth();
+
+        if lease.state != LeaseState::Active {
+            panic!("Lease with ID {} is not active", lease_id);
+        }
+
+        let order = OrderBookContract::get_order(env.clone(), lease.order_id);
+
+        let term = lease.end_block - lease.start_block;
+        let now = env.ledger().sequence().min(lease.end_block);
+        let billed = (now - lease.start_block)
+            .max(order.terms.min_billing_blocks)
+            .min(term);
+
+        let payout = (lease.price * billed as u128 / term as u128).max(lease.claimed);
+        let refund = lease.price - payout;
+
+        let unclaimed = payout - lease.claimed;
+        OrderBookContract::pay_provider(env.clone(), &mut lease, unclaimed);
+        if refund > 0 {
+            OrderBookContract::escrow_client(&env).unlock_tokens(
+                &lease.token,
+                &lease.tenant,
+                &OrderBookContract::to_escrow_amount(refund),
+            );
+        }
+
+        lease.state = LeaseState::Canceled;
+        lease.end_block = now;
+        lease.price = payout;
+        OrderBookContract::release_capacity(env.clone(), &lease);
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+        OrderBookContract::publish_lease_event(&env, "lease_canceled", lease_id, &lease);
+    }
+
+    // Extends an open lease by the given number of blocks. The tenant funds the
+    // extension at the lease's agreed rate.
+    pub fn extend_lease(env: Env, lease_id: u64, extra_blocks: u32) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+        lease.tenant.require_auth();
+
+        if lease.state != LeaseState::Initiate && lease.state != LeaseState::Active {
+            panic!("Lease with ID {} is already closed", lease_id);
+        }
+
+        if extra_blocks == 0 {
+            panic!("Extension must be at least one block");
+        }
+
+        let cost = OrderBookContract::extension_cost(env.clone(), &lease, extra_blocks);
+        OrderBookContract::escrow_client(&env).lock(
+            &lease.token,
+            &lease.tenant,
+            &OrderBookContract::to_escrow_amount(cost),
+        );
+
+        lease.price += cost;
+        lease.end_block += extra_blocks;
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+    }
+
+    // Lets the lease extend itself by `extra_blocks` up to `max_renewals` times
+    // when it ends. Passing zero renewals turns auto-renewal off.
+    pub fn set_auto_renew(env: Env, lease_id: u64, extra_blocks: u32, max_renewals: u32) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+        lease.tenant.require_auth();
+
+        if lease.state != LeaseState::Initiate && lease.state != LeaseState::Active {
+            panic!("Lease with ID {} is already closed", lease_id);
+        }
+
+        if max_renewals > 0 && extra_blocks == 0 {
+            panic!("Extension must be at least one block");
+        }
+
+        lease.auto_renew = AutoRenew {
+            extra_blocks,
+            renewals_left: max_renewals,
+        };
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+    }
+
+    // Cancels a lease and refunds the tenant in full, except for the deposit of
+    // a forward lease the tenant cancels. Either party can cancel a lease that
+    // was never activated; once active only the provider can walk away from
+    // it.
+    pub fn cancel_lease(env: Env, from: Address, lease_id: u64) {
+        from.require_auth();
+
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        let provider = ProviderRegistryContractClient::new(&env, &provider_registry)
+            .get_provider(&lease.provider_id);
+
+        let allowed = match lease.state {
+            LeaseState::Initiate => from == lease.tenant || from == provider.address,
+            LeaseState::Active => from == provider.address,
+            _ => panic!("Lease with ID {} is already closed", lease_id),
+        };
+
+        if !allowed {
+            panic!("Not allowed to cancel lease with ID {}", lease_id);
+        }
+
+        // a provider walking away from a lease it never activated loses its
+        // bond to the tenant
+        if lease.state == LeaseState::Initiate {
+            let mut bid = OrderBookContract::get_bid(env.clone(), lease.bid_id);
+            if from == provider.address {
+                OrderBookContract::forfeit_bond(env.clone(), &lease, &mut bid);
+            } else {
+                OrderBookContract::release_bond(env.clone(), &lease.token, &mut bid);
+            }
+            // and a tenant backing out of a reservation before it starts
+            // loses its deposit
+            if from == lease.tenant
+                && lease.deposit > 0
+                && env.ledger().sequence() < lease.start_block
+            {
+                let deposit = lease.deposit;
+                OrderBookContract::pay_provider(env.clone(), &mut lease, deposit);
+            }
+            env.storage()
+                .instance()
+                .set(&BidStates::Bids(lease.bid_id), &bid);
+        }
+
+        OrderBookContract::refund_lease(env, lease_id, &mut lease);
+    }
+
+    // Cancels a lease the provider failed to activate within the bond's
+    // activation window, refunds the tenant and pays them the provider's bond.
+    // Callable by anyone.
+    pub fn forfeit_bid_bond(env: Env, lease_id: u64) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+        // an unfunded forward lease can not be activated, it lapses instead
+        if lease.state != LeaseState::Initiate || lease.unfunded > 0 {
+            panic!("Lease with ID {} is not awaiting activation", lease_id);
+        }
+
+        let mut bid = OrderBookContract::get_bid(env.clone(), lease.bid_id);
+        if bid.bond == 0 {
+            panic!("Lease with ID {} has no bond", lease_id);
+        }
+
+        let activation_blocks = OrderBookContract::get_bid_bond(env.clone()).activation_blocks;
+        if env.ledger().sequence() <= lease.start_block + activation_blocks {
+            panic!("Activation window of lease {} is still open", lease_id);
+        }
+
+        OrderBookContract::forfeit_bond(env.clone(), &lease, &mut bid);
+        env.storage()
+            .instance()
+            .set(&BidStates::Bids(lease.bid_id), &bid);
+
+        OrderBookContract::refund_lease(env, lease_id, &mut lease);
+    }
+
+    // Locks the rest of a forward lease's price once its funding window opens.
+    // Callable by anyone; it fails if the tenant's escrow balance can not
+    // cover it.
+    pub fn fund_lease(env: Env, lease_id: u64) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+        let OrderClass::Forward(forward) = &lease.class else {
+            panic!("Lease with ID {} is not a forward lease", lease_id);
+        };
+
+        if lease.state != LeaseState::Initiate || lease.unfunded == 0 {
+            panic!("Lease with ID {} is not awaiting funding", lease_id);
+        }
+
+        let now = env.ledger().sequence();
+        if now >= lease.start_block {
+            panic!("Funding window of lease {} is closed", lease_id);
+        }
+
+        if now + forward.funding_blocks < lease.start_block {
+            panic!("Funding window of lease {} has not opened", lease_id);
+        }
+
+        // the tenant agreed to fund the reservation when ordering it
+        OrderBookContract::escrow_client(&env).lock_from_order_book(
+            &lease.token,
+            &lease.tenant,
+            &OrderBookContract::to_escrow_amount(lease.unfunded),
+        );
+        lease.unfunded = 0;
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+        OrderBookContract::publish_lease_event(&env, "lease_funded", lease_id, &lease);
+    }
+
+    // Cancels a forward lease that was not fully funded by its start. The
+    // provider keeps the deposit and gets its bond back. Callable by anyone.
+    pub fn lapse_lease(env: Env, lease_id: u64) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+        if lease.state != LeaseState::Initiate || lease.unfunded == 0 {
+            panic!("Lease with ID {} is not awaiting funding", lease_id);
+        }
+
+        if env.ledger().sequence() < lease.start_block {
+            panic!("Lease with ID {} can still be funded", lease_id);
+        }
+
+        let mut bid = OrderBookContract::get_bid(env.clone(), lease.bid_id);
+        OrderBookContract::release_bond(env.clone(), &lease.token, &mut bid);
+        env.storage()
+            .instance()
+            .set(&BidStates::Bids(lease.bid_id), &bid);
+
+        let deposit = lease.deposit;
+        OrderBookContract::pay_provider(env.clone(), &mut lease, deposit);
+
+        OrderBookContract::refund_lease(env, lease_id, &mut lease);
+    }
+
+    // Cancels the lease and returns its unclaimed funds to the tenant.
+    fn refund_lease(env: Env, lease_id: u64, lease: &mut Lease) {
+        lease.state = LeaseState::Canceled;
+        OrderBookContract::release_capacity(env.clone(), lease);
+
+        // earnings the provider already claimed are not clawed back
+        let refund = lease.price - lease.claimed - lease.unfunded;
+        if refund > 0 {
+            OrderBookContract::escrow_client(&env).unlock_tokens(
+                &lease.token,
+                &lease.tenant,
+                &OrderBookContract::to_escrow_amount(refund),
+            );
+        }
+        lease.price = lease.claimed;
+        lease.unfunded = 0;
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &*lease);
+        OrderBookContract::publish_lease_event(&env, "lease_canceled", lease_id, lease);
+    }
+
+    // Names spot leases of the bidding provider to preempt if the given bid on
+    // a regular order is matched and the provider lacks the capacity for it.
+    // Nothing is preempted unless the bid wins.
+    pub fn set_bid_preemptions(env: Env, bid_id: u64, lease_ids: Vec<u64>) {
+        let bid = OrderBookContract::get_bid(env.clone(), bid_id);
+        if bid.state != BidState::Active {
+            panic!("Bid with ID {} is not active", bid_id);
+        }
+
+        OrderBookContract::require_provider_auth(env.clone(), bid.provider);
+
+        let order = OrderBookContract::get_order(env.clone(), bid.order_id);
+        if order.terms.class != OrderClass::Regular || order.state != OrderState::Active {
+            panic!("Bid with ID {} can not preempt leases", bid_id);
+        }
+
+        for lease_id in lease_ids.iter() {
+            let lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+            if lease.class != OrderClass::Spot {
+                panic!("Lease with ID {} is not a spot lease", lease_id);
+            }
+
+            if lease.state != LeaseState::Initiate && lease.state != LeaseState::Active {
+                panic!("Lease with ID {} is already closed", lease_id);
+            }
+
+            if lease.provider_id != bid.provider {
+                panic!("Bid with ID {} can not preempt lease {}", bid_id, lease_id);
+            }
+
+            if !OrderBookContract::outbids(&bid, &order, &lease) {
+                panic!(
+                    "Bid with ID {} does not pay more than lease {}",
+                    bid_id, lease_id
+                );
+            }
+        }
+
+        env.storage()
+            .instance()
+            .set(&BidStates::Preemptions(bid_id), &lease_ids);
+    }
+
+    // Whether the bid pays more per block than the lease.
+    fn outbids(bid: &ProviderBid, order: &OrderRequest, lease: &Lease) -> bool {
+        let term = (lease.end_block - lease.start_block) as u128;
+        bid.bid_price * bid.quantity as u128 * term > lease.price * order.number_of_blocks as u128
+    }
+
+    // Spot leases named for the bid that it can still preempt.
+    fn preemptible_leases(
+        env: Env,
+        bid_id: u64,
+        bid: &ProviderBid,
+        order: &OrderRequest,
+    ) -> Vec<(u64, Lease)> {
+        let lease_ids: Vec<u64> = env
+            .storage()
+            .instance()
+            .get(&BidStates::Preemptions(bid_id))
+            .unwrap_or(Vec::new(&env));
+
+        let mut leases = Vec::new(&env);
+        for lease_id in lease_ids.iter() {
+            let lease = OrderBookContract::get_lease(env.clone(), lease_id);
+            if (lease.state == LeaseState::Initiate || lease.state == LeaseState::Active)
+                && OrderBookContract::outbids(bid, order, &lease)
+            {
+                leases.push_back((lease_id, lease));
+            }
+        }
+        leases
+    }
+
+    fn preemptible_capacity(
+        env: Env,
+        bid_id: u64,
+        bid: &ProviderBid,
+        order: &OrderRequest,
+    ) -> Capacity {
+        let mut capacity = Capacity::zero();
+        for (_, lease) in OrderBookContract::preemptible_leases(env.clone(), bid_id, bid, order) {
+            capacity = capacity.plus(&OrderBookContract::lease_capacity(env.clone(), &lease));
+        }
+        capacity
+    }
+
+    // Preempts the spot leases named for a matched bid, in order, until its
+    // provider has the capacity for it.
+    fn preempt_for_bid(env: Env, bid_id: u64, bid: &ProviderBid, order: &OrderRequest) {
+        let required = order.spec.spec.capacity(bid.quantity);
+
+        for (lease_id, mut lease) in
+            OrderBookContract::preemptible_leases(env.clone(), bid_id, bid, order)
+        {
+            if OrderBookContract::has_capacity(
+                env.clone(),
+                bid.provider,
+                &required,
+                &Capacity::zero(),
+            ) {
+                break;
+            }
+
+            OrderBookContract::preempt_lease(env.clone(), lease_id, &mut lease);
+        }
+
+        env.storage()
+            .instance()
+            .remove(&BidStates::Preemptions(bid_id));
+    }
+
+    // Ends a spot lease for a better-paying regular lease of its provider. The
+    // spot tenant pays for the blocks served and gets the rest back.
+    fn preempt_lease(env: Env, lease_id: u64, lease: &mut Lease) {
+        // spot terms allow preemption, so the provider keeps its bond
+        let mut bid = OrderBookContract::get_bid(env.clone(), lease.bid_id);
+        if bid.bond > 0 {
+            OrderBookContract::release_bond(env.clone(), &lease.token, &mut bid);
+            env.storage()
+                .instance()
+                .set(&BidStates::Bids(lease.bid_id), &bid);
+        }
+
+        // a lease that never started has served nothing
+        let now = env.ledger().sequence();
+        let unclaimed = OrderBookContract::earned_until(lease, now);
+        OrderBookContract::pay_provider(env.clone(), lease, unclaimed);
+
+        let refund = lease.price - lease.claimed;
+        if refund > 0 {
+            OrderBookContract::escrow_client(&env).unlock_tokens(
+                &lease.token,
+                &lease.tenant,
+                &OrderBookContract::to_escrow_amount(refund),
+            );
+        }
+
+        OrderBookContract::release_capacity(env.clone(), lease);
+        lease.state = LeaseState::Canceled;
+        lease.end_block = now.clamp(lease.start_block, lease.end_block);
+        lease.price = lease.claimed;
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &*lease);
+        OrderBookContract::publish_lease_event(&env, "lease_preempted", lease_id, lease);
+    }
+
+    // Moves an active lease off a provider that went into maintenance or was
+    // suspended. The provider is paid for the blocks served so far and the
+    // rest of the escrowed budget funds a replacement order for the remaining
+    // blocks with the same spec. Returns the replacement order's ID.
+    pub fn migrate_lease(env: Env, lease_id: u64) -> u64 {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+        lease.tenant.require_auth();
+
+        if lease.state != LeaseState::Active {
+            panic!("Lease with ID {} is not active", lease_id);
+        }
+
+        let provider_registry = OrderBookContract::get_provider_registry(env.clone());
+        let provider = ProviderRegistryContractClient::new(&env, &provider_registry)
+            .get_provider(&lease.provider_id);
+        if provider.status != ProviderStatus::Maintanance
+            && provider.status != ProviderStatus::Suspended
+        {
+            panic!(
+                "Provider {} is still serving lease {}",
+                lease.provider_id, lease_id
+            );
+        }
+
+        let now = env.ledger().sequence();
+        if now >= lease.end_block {
+            panic!("Lease with ID {} has no blocks left", lease_id);
+        }
+
+        // settle the failed provider pro rata
+        let unclaimed = OrderBookContract::earned_until(&lease, now);
+        OrderBookContract::pay_provider(env.clone(), &mut lease, unclaimed);
+        let budget = lease.price - lease.claimed;
+
+        let order = OrderBookContract::get_order(env.clone(), lease.order_id);
+        let bid = OrderBookContract::get_bid(env.clone(), lease.bid_id);
+
+        let replacement = OrderRequest {
+            tenant: lease.tenant.clone(),
+            token: lease.token.clone(),
+            max_price: budget / bid.quantity as u128,
+            state: OrderState::Active,
+            spec: Specification {
+                quantity: bid.quantity,
+                max_price: budget / bid.quantity as u128,
+                ..order.spec.clone()
+            },
+            number_of_blocks: lease.end_block - now,
+            bid_deadline: now + (order.bid_deadline - order.created_at),
+            auction: AuctionMode::Open,
+            filled: 0,
+            terms: OrderTerms {
+                // the reserved window is under way, so the replacement runs now
+                class: match order.terms.class {
+                    OrderClass::Forward(_) => OrderClass::Regular,
+                    class => class,
+                },
+                ..order.terms
+            },
+            created_at: now,
+        };
+
+        // the budget is already locked, release what does not divide evenly
+        let remainder = budget - replacement.max_price * bid.quantity as u128;
+        if remainder > 0 {
+            OrderBookContract::escrow_client(&env).unlock_tokens(
+                &lease.token,
+                &lease.tenant,
+                &OrderBookContract::to_escrow_amount(remainder),
+            );
+        }
+
+        let order_id = OrderBookContract::store_new_order(env.clone(), replacement);
+
+        OrderBookContract::release_capacity(env.clone(), &lease);
+        lease.state = LeaseState::Canceled;
+        lease.end_block = now;
+        lease.price = lease.claimed;
+        lease.successor_order = Some(order_id);
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+        OrderBookContract::publish_lease_event(&env, "lease_migrated", lease_id, &lease);
+
+        order_id
+    }
+
+    // Freezes the final payout of an ended lease until the arbiter resolves the
+    // dispute. The evidence hash points to the tenant's off-chain evidence.
+    pub fn open_dispute(env: Env, lease_id: u64, evidence: BytesN<32>) {
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+        lease.tenant.require_auth();
+
+        if lease.state != LeaseState::Ended {
+            panic!("Lease with ID {} has not ended", lease_id);
+        }
+
+        if env.ledger().sequence() > lease.end_block + lease.dispute_window {
+            panic!("Dispute window of lease {} is closed", lease_id);
+        }
+
+        lease.state = LeaseState::Disputed;
+        lease.evidence = Some(evidence);
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+        OrderBookContract::publish_lease_event(&env, "lease_disputed", lease_id, &lease);
+    }
+
+    // Settles a disputed lease by paying the provider the given share of the
+    // frozen payout and returning the rest to the tenant.
+    pub fn resolve_dispute(env: Env, lease_id: u64, provider_amount: u128) {
+        OrderBookContract::get_arbiter(env.clone()).require_auth();
+
+        let mut lease = OrderBookContract::get_lease(env.clone(), lease_id);
+
+        if lease.state != LeaseState::Disputed {
+            panic!("Lease with ID {} is not disputed", lease_id);
+        }
+
+        let frozen = lease.price - lease.claimed;
+        if provider_amount > frozen {
+            panic!("Provider share exceeds the disputed amount");
+        }
+
+        OrderBookContract::pay_provider(env.clone(), &mut lease, provider_amount);
+        if frozen > provider_amount {
+            OrderBookContract::escrow_client(&env).unlock_tokens(
+                &lease.token,
+                &lease.tenant,
+                &OrderBookContract::to_escrow_amount(frozen - provider_amount),
+            );
+        }
+
+        lease.state = LeaseState::Completed;
+        lease.price = lease.claimed;
+
+        env.storage()
+            .persistent()
+            .set(&BidStates::Leases(lease_id), &lease);
+        OrderBookContract::publish_lease_event(&env, "lease_completed", lease_id, &lease);
+    }
+
+    pub fn get_lease(env: Env, lease_id: u64) -> Lease {
+        env.storage()
+            .persistent()
+            .get(&BidStates::Leases(lease_id))
+            .unwrap_or_else(|| panic!("Lease with ID {} not found", lease_id))
+    }
+
+    // Lists a tenant's or provider's leases in creation order, starting at the
+    // given offset into their leases.
+    pub fn list_leases(env: Env, owner: LeaseOwner, cursor: u32, limit: u32) -> Vec<(u64, Lease)> {
+        let key = match owner {
+            LeaseOwner::Tenant(tenant) => BidStates::TenantLeases(tenant),
+            LeaseOwner::Provider(provider_id) => BidStates::ProviderLeases(provider_id),
+        };
+
+        let lease_ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or(Vec::new(&env));
+
+        let mut leases = Vec::new(&env);
+        let end = lease_ids.len().min(cursor.saturating_add(limit));
+        for i in cursor..end {
+            let lease_id = lease_ids.get_unchecked(i);
+            leases.push_back((
+                lease_id,
+                OrderBookContract::get_lease(env.clone(), lease_id),
+            ));
+        }
+
+        leases
+    }
+
+    // Returns the leases created for an order, one per accepted bid.
+    pub fn get_lease_by_order(env: Env, order_id: u64) -> Vec<(u64, Lease)> {
+        let lease_ids: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&BidStates::OrderLeases(order_id))
+            .unwrap_or(Vec::new(&env));
+
+        let mut leases = Vec::new(&env);
+        for lease_id in lease_ids.iter() {
+            leases.push_back((
+                lease_id,
+                OrderBookContract::get_lease(env.clone(), lease_id),
+            ));
+        }
 
-        lease
+        leases
     }
 }
 
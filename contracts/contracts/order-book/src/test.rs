@@ -1,24 +1,201 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::{testutils::Ledger, vec, Env};
+use soroban_sdk::{
+    testutils::{Address as _, Events, Ledger},
+    token::{StellarAssetClient as TokenAdminClient, TokenClient},
+    vec, Bytes, BytesN, Env, Symbol,
+};
+
+struct OrderBookTest<'a> {
+    client: OrderBookContractClient<'a>,
+    provider_registry: ProviderRegistryContractClient<'a>,
+    escrow: EscrowContractClient<'a>,
+    token: TokenClient<'a>,
+    token_admin: TokenAdminClient<'a>,
+}
+
+impl OrderBookTest<'_> {
+    // Helper function to create a tenant with funds deposited in escrow
+    fn create_tenant(&self, env: &Env) -> Address {
+        let tenant = Address::generate(env);
+        self.token_admin.mint(&tenant, &10_000_000);
+        self.escrow
+            .deposit(&self.token.address, &tenant, &10_000_000);
+        tenant
+    }
+
+    // Helper function to deposit funds in escrow for a provider to bond bids
+    fn fund_provider(&self, provider_id: u64) -> Address {
+        let provider = self.provider_registry.get_provider(&provider_id).address;
+        self.token_admin.mint(&provider, &1_000_000);
+        self.escrow
+            .deposit(&self.token.address, &provider, &1_000_000);
+        provider
+    }
+}
+
+// Helper function to setup the order book with a provider registry, escrow
+// and payment token
+fn create_order_book<'a>(env: &Env) -> OrderBookTest<'a> {
+    env.mock_all_auths();
+
+    let provider_registry_id = env.register(::provider_registry::ProviderRegistryContract, ());
+    let escrow_id = env.register(
+        ::escrow::EscrowContract,
+        (&provider_registry_id, &Address::generate(env)),
+    );
+    let sac = env.register_stellar_asset_contract_v2(Address::generate(env));
+    let contract_id = env.register(
+        OrderBookContract,
+        (&provider_registry_id, &escrow_id, &Address::generate(env)),
+    );
+    EscrowContractClient::new(env, &escrow_id).set_order_book(&contract_id);
+    OrderBookContractClient::new(env, &contract_id).add_accepted_token(&sac.address());
+
+    OrderBookTest {
+        client: OrderBookContractClient::new(env, &contract_id),
+        provider_registry: ProviderRegistryContractClient::new(env, &provider_registry_id),
+        escrow: EscrowContractClient::new(env, &escrow_id),
+        token: TokenClient::new(env, &sac.address()),
+        token_admin: TokenAdminClient::new(env, &sac.address()),
+    }
+}
+
+// Helper function to register a provider with the given trust level
+fn register_provider(
+    env: &Env,
+    provider_registry: &ProviderRegistryContractClient,
+    trust_level: provider_registry::TrustLevel,
+) -> u64 {
+    let provider_id = provider_registry.add_provider(&Address::generate(env));
+    provider_registry.set_trust_level(&Address::generate(env), &provider_id, &trust_level);
+    provider_registry.set_provider_status(
+        &Address::generate(env),
+        &provider_id,
+        &ProviderStatus::Active,
+    );
+    provider_id
+}
+
+// Helper function to build a filter matching every order
+fn any_order(env: &Env) -> OrderFilter {
+    OrderFilter {
+        states: Vec::new(env),
+        tenant: None,
+        token: None,
+        min_price: None,
+        max_price: None,
+    }
+}
+
+// Helper function to build a typed resource specification
+fn resource_spec(env: &Env) -> ResourceSpec {
+    ResourceSpec {
+        cpu_millis: 4000,
+        memory_mb: 8192,
+        storage_gb: 100,
+        gpus: 1,
+        region: String::from_str(env, "eu-west"),
+        image_digest: BytesN::from_array(env, &[9; 32]),
+        ports: vec![env, 80, 443],
+        capabilities: vec![env, Symbol::new(env, "ssd"), Symbol::new(env, "ipv6")],
+    }
+}
+
+// Helper function to create an order for a tenant and match a single bid
+fn create_matched_lease(
+    env: &Env,
+    order_book: &OrderBookTest,
+    tenant: &Address,
+    provider_id: u64,
+    bid_price: u128,
+    number_of_blocks: u32,
+) -> u64 {
+    let client = &order_book.client;
+    let order_id = client.create_order(
+        tenant,
+        &order_book.token.address,
+        &100_000,
+        &number_of_blocks,
+        &1,
+        &OrderSpec::Text(String::from_str(env, "cpu:4,memory:8G")),
+        &vec![env, TrustLevel::One],
+        &(env.ledger().sequence() + 100),
+        &AuctionMode::Open,
+        &order_terms(env, 0),
+    );
+    let bid_id = client.place_bid(&order_id, &provider_id, &bid_price, &1);
+    client.accept_bid(&bid_id)
+}
+
+// Helper function to build order terms without an SLA
+fn order_terms(env: &Env, min_billing_blocks: u32) -> OrderTerms {
+    OrderTerms {
+        min_billing_blocks,
+        sla: SlaTerms {
+            min_uptime_bps: 0,
+            max_latency_ms: 0,
+            downtime_budget: 0,
+            penalties: Vec::new(env),
+        },
+        class: OrderClass::Regular,
+    }
+}
+
+// Helper function to build a deployment manifest hash
+fn manifest_hash(env: &Env) -> BytesN<32> {
+    BytesN::from_array(env, &[5; 32])
+}
+
+// Helper function to compute a sealed bid commitment
+fn bid_commitment(env: &Env, bid_price: u128, salt: &BytesN<32>) -> BytesN<32> {
+    let mut payload = Bytes::from_array(env, &bid_price.to_be_bytes());
+    payload.append(&salt.clone().into());
+    env.crypto().sha256(&payload).into()
+}
+
+// Helper function to check that the order book published an event in the last
+// invocation
+fn has_event(env: &Env, contract: &Address, topics: Vec<Val>, data: Val) -> bool {
+    env.events()
+        .all()
+        .iter()
+        .any(|(address, event_topics, event_data)| {
+            address == *contract
+                && event_topics == topics
+                && vec![env, event_data] == vec![env, data]
+        })
+}
+
+fn set_ledger_sequence(env: &Env, sequence_number: u32) {
+    let mut ledger = env.ledger().get();
+    ledger.sequence_number = sequence_number;
+    env.ledger().set(ledger);
+}
 
 #[test]
 fn test_create_order() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
 
     // Create trust levels
     let trust_levels = vec![&env, TrustLevel::One, TrustLevel::Two];
 
     // Create order
     let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &100_000, // max_price
         &1000,    // number_of_blocks
         &5,       // quantity
-        &String::from_str(&env, "cpu:4,memory:8G,storage:100G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G,storage:100G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     assert_eq!(order_id, 1); // First order should have ID 1
@@ -34,7 +211,7 @@ fn test_create_order() {
     assert_eq!(order.spec.max_price, 100_000);
     assert_eq!(
         order.spec.spec,
-        String::from_str(&env, "cpu:4,memory:8G,storage:100G")
+        OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G,storage:100G"))
     );
     assert_eq!(order.spec.trust_levels.len(), 2);
 }
@@ -42,36 +219,52 @@ fn test_create_order() {
 #[test]
 fn test_create_multiple_orders() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
 
     let trust_levels = vec![&env, TrustLevel::Three];
 
     // Create first order
     let order_id1 = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &50_000,
         &500,
         &2,
-        &String::from_str(&env, "cpu:2,memory:4G,storage:50G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:2,memory:4G,storage:50G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // Create second order
     let order_id2 = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &75_000,
         &750,
         &3,
-        &String::from_str(&env, "cpu:3,memory:6G,storage:75G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:3,memory:6G,storage:75G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // Create third order
     let order_id3 = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &100_000,
         &1000,
         &4,
-        &String::from_str(&env, "cpu:4,memory:8G,storage:100G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G,storage:100G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // Check order IDs
@@ -80,25 +273,31 @@ fn test_create_multiple_orders() {
     assert_eq!(order_id3, 3);
 
     // Check order count by listing orders
-    let orders = client.list_orders();
+    let orders = client.list_orders(&any_order(&env), &0, &100);
     assert_eq!(orders.len(), 3);
 }
 
 #[test]
 fn test_update_order_to_closed() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
 
     let trust_levels = vec![&env, TrustLevel::Four];
 
     // Create order
     let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &60_000,
         &600,
         &3,
-        &String::from_str(&env, "cpu:3,memory:6G,storage:60G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:3,memory:6G,storage:60G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // Verify initial state
@@ -116,49 +315,91 @@ fn test_update_order_to_closed() {
 #[test]
 fn test_place_bid() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider_id =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
 
     let trust_levels = vec![&env, TrustLevel::Five];
 
     // Create order
     let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &80_000,
         &800,
         &4,
-        &String::from_str(&env, "cpu:4,memory:8G,storage:80G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G,storage:80G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // Place bid
-    let provider_id = 42; // Mock provider ID
     let bid_price = 70_000;
-    let bid_id = client.place_bid(&order_id, &provider_id, &bid_price);
+    let bid_id = client.place_bid(&order_id, &provider_id, &bid_price, &1);
 
     assert_eq!(bid_id, 1); // First bid should have ID 1
 }
 
+#[test]
+#[should_panic(expected = "Provider with ID 42 not found")]
+fn test_place_bid_unregistered_provider() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &80_000,
+        &800,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    client.place_bid(&order_id, &42, &70_000, &1);
+}
+
 #[test]
 fn test_place_multiple_bids() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider3 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
 
     let trust_levels = vec![&env, TrustLevel::Two];
 
     // Create order
     let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &90_000,
         &900,
         &4,
-        &String::from_str(&env, "cpu:4,memory:8G,storage:90G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G,storage:90G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // Place multiple bids
-    let bid_id1 = client.place_bid(&order_id, &101, &85_000);
-    let bid_id2 = client.place_bid(&order_id, &102, &82_000);
-    let bid_id3 = client.place_bid(&order_id, &103, &80_000);
+    let bid_id1 = client.place_bid(&order_id, &provider1, &85_000, &1);
+    let bid_id2 = client.place_bid(&order_id, &provider2, &82_000, &1);
+    let bid_id3 = client.place_bid(&order_id, &provider3, &80_000, &1);
 
     // Check bid IDs
     assert_eq!(bid_id1, 1);
@@ -170,18 +411,22 @@ fn test_place_multiple_bids() {
 #[should_panic(expected = "Order with ID 999 not found")]
 fn test_place_bid_invalid_order() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
 
     // Try to place a bid on a non-existent order
-    client.place_bid(&999, &1, &50_000);
+    client.place_bid(&999, &1, &50_000, &1);
 }
 
 #[test]
 fn test_accept_bid() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider_id =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
 
     // Mock the current block number
     let mut ledger = env.ledger().get();
@@ -193,17 +438,21 @@ fn test_accept_bid() {
 
     // Create order
     let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &70_000,
         &700,
         &3,
-        &String::from_str(&env, "cpu:3,memory:6G,storage:70G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:3,memory:6G,storage:70G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // Place bid
-    let provider_id = 201;
     let bid_price = 65_000;
-    let bid_id = client.place_bid(&order_id, &provider_id, &bid_price);
+    let bid_id = client.place_bid(&order_id, &provider_id, &bid_price, &3);
 
     // Accept bid
     client.accept_bid(&bid_id);
@@ -212,43 +461,57 @@ fn test_accept_bid() {
     let order = client.get_order(&order_id);
     assert!(matches!(order.state, OrderState::Complete));
 
-    // The max_price field should be updated to the accepted bid price
-    assert_eq!(order.max_price, bid_price);
+    // The order keeps its max price, the lease is priced at the bid
+    assert_eq!(order.max_price, 70_000);
 
-    // Note: Ideally we would verify the lease was created, but the contract doesn't
-    // provide a method to retrieve lease information
+    // Verify the lease was created
+    let leases = client.get_lease_by_order(&order_id);
+    assert_eq!(leases.len(), 1);
+    assert_eq!(leases.get(0).unwrap().1.provider_id, provider_id);
+    assert_eq!(leases.get(0).unwrap().1.price, bid_price * 3);
 }
 
 #[test]
 fn test_full_order_lifecycle() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider3 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
 
     // Mock the current block number
     let mut ledger = env.ledger().get();
     ledger.sequence_number = 500;
     ledger.timestamp = 12345;
+    env.ledger().set(ledger);
 
     // Create trust levels
     let trust_levels = vec![&env, TrustLevel::One, TrustLevel::Two];
 
     // 1. Create an order
     let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &100_000,
         &1000,
         &5,
-        &String::from_str(&env, "cpu:4,memory:8G,storage:100G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G,storage:100G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     let order = client.get_order(&order_id);
     assert!(matches!(order.state, OrderState::Active));
 
     // 2. Place multiple bids
-    client.place_bid(&order_id, &301, &95_000);
-    client.place_bid(&order_id, &302, &90_000);
-    let bid_id3 = client.place_bid(&order_id, &303, &85_000); // Lowest price
+    client.place_bid(&order_id, &provider1, &95_000, &5);
+    client.place_bid(&order_id, &provider2, &90_000, &5);
+    let bid_id3 = client.place_bid(&order_id, &provider3, &85_000, &5); // Lowest price
 
     // 3. Accept the lowest bid
     client.accept_bid(&bid_id3);
@@ -256,15 +519,22 @@ fn test_full_order_lifecycle() {
     // 4. Verify order state changed to complete
     let updated_order = client.get_order(&order_id);
     assert!(matches!(updated_order.state, OrderState::Complete));
-    assert_eq!(updated_order.max_price, 85_000); // Should be updated to the accepted bid price
+    assert_eq!(updated_order.max_price, 100_000);
+    let leases = client.get_lease_by_order(&order_id);
+    assert_eq!(leases.get(0).unwrap().1.price, 85_000 * 5);
 
     // 5. Create another order
     let order_id2 = client.create_order(
+        &tenant,
+        &order_book.token.address,
         &120_000,
         &1200,
         &6,
-        &String::from_str(&env, "cpu:6,memory:12G,storage:120G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:6,memory:12G,storage:120G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // 6. Close this order without accepting any bids
@@ -277,57 +547,73 @@ fn test_full_order_lifecycle() {
 #[test]
 fn test_list_orders() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
 
     // Initially, there should be no orders
-    let orders = client.list_orders();
+    let orders = client.list_orders(&any_order(&env), &0, &100);
     assert_eq!(orders.len(), 0);
 
     let trust_levels = vec![&env, TrustLevel::Three];
 
     // Create several orders
     client.create_order(
+        &tenant,
+        &order_book.token.address,
         &50_000,
         &500,
         &2,
-        &String::from_str(&env, "cpu:2,memory:4G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:2,memory:4G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     client.create_order(
+        &tenant,
+        &order_book.token.address,
         &60_000,
         &600,
         &3,
-        &String::from_str(&env, "cpu:3,memory:6G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:3,memory:6G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     client.create_order(
+        &tenant,
+        &order_book.token.address,
         &70_000,
         &700,
         &4,
-        &String::from_str(&env, "cpu:4,memory:8G"),
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
         &trust_levels,
+        &1_000,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
     );
 
     // List should now contain 3 orders
-    let orders = client.list_orders();
+    let orders = client.list_orders(&any_order(&env), &0, &100);
     assert_eq!(orders.len(), 3);
 
     // Verify details of each order
-    assert_eq!(orders.get(0).unwrap().max_price, 50_000);
-    assert_eq!(orders.get(1).unwrap().max_price, 60_000);
-    assert_eq!(orders.get(2).unwrap().max_price, 70_000);
+    assert_eq!(orders.get(0).unwrap().1.max_price, 50_000);
+    assert_eq!(orders.get(1).unwrap().1.max_price, 60_000);
+    assert_eq!(orders.get(2).unwrap().1.max_price, 70_000);
 
     // Close an order
     client.update_order_to_closed(&2);
 
     // List should still contain 3 orders, but one should be closed
-    let updated_orders = client.list_orders();
+    let updated_orders = client.list_orders(&any_order(&env), &0, &100);
     assert_eq!(updated_orders.len(), 3);
     assert!(matches!(
-        updated_orders.get(1).unwrap().state,
+        updated_orders.get(1).unwrap().1.state,
         OrderState::Closed
     ));
 }
@@ -336,8 +622,8 @@ fn test_list_orders() {
 #[should_panic(expected = "Order with ID 999 not found")]
 fn test_get_nonexistent_order() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
 
     // Try to get an order that doesn't exist
     client.get_order(&999);
@@ -347,9 +633,3349 @@ fn test_get_nonexistent_order() {
 #[should_panic(expected = "Order with ID 999 not found")]
 fn test_update_nonexistent_order() {
     let env = Env::default();
-    let contract_id = env.register(OrderBookContract, {});
-    let client = OrderBookContractClient::new(&env, &contract_id);
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
 
     // Try to update an order that doesn't exist
     client.update_order_to_closed(&999);
 }
+
+#[test]
+#[should_panic(expected = "Bid deadline must be in the future")]
+fn test_create_order_past_deadline() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    set_ledger_sequence(&env, 100);
+
+    client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &50_000,
+        &500,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:2,memory:4G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Order with ID 1 is not open for bidding")]
+fn test_place_bid_after_deadline() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &50_000,
+        &500,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:2,memory:4G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    set_ledger_sequence(&env, 101);
+    client.place_bid(&order_id, &1, &40_000, &1);
+}
+
+#[test]
+fn test_settle_order() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+
+    let trusted = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let new_provider =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::Two);
+    let unqualified =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::Five);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One, TrustLevel::Two],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    // Unqualified provider bids lowest but cannot win
+    client.place_bid(&order_id, &unqualified, &50_000, &1);
+
+    // Two qualified providers tie on price, higher trust wins
+    set_ledger_sequence(&env, 10);
+    client.place_bid(&order_id, &new_provider, &80_000, &1);
+    set_ledger_sequence(&env, 20);
+    let winning_bid = client.place_bid(&order_id, &trusted, &80_000, &1);
+    client.place_bid(&order_id, &trusted, &90_000, &1);
+
+    set_ledger_sequence(&env, 101);
+    assert_eq!(client.settle_order(&order_id), vec![&env, winning_bid]);
+
+    let order = client.get_order(&order_id);
+    assert!(matches!(order.state, OrderState::Complete));
+    assert_eq!(order.max_price, 100_000);
+    let leases = client.get_lease_by_order(&order_id);
+    assert_eq!(leases.get(0).unwrap().1.price, 80_000);
+}
+
+#[test]
+fn test_settle_order_tie_break_by_bid_time() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider1 = register_provider(
+        &env,
+        provider_registry,
+        provider_registry::TrustLevel::Three,
+    );
+    let provider2 = register_provider(
+        &env,
+        provider_registry,
+        provider_registry::TrustLevel::Three,
+    );
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::Three],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    set_ledger_sequence(&env, 10);
+    let earliest_bid = client.place_bid(&order_id, &provider2, &70_000, &1);
+    set_ledger_sequence(&env, 20);
+    client.place_bid(&order_id, &provider1, &70_000, &1);
+
+    set_ledger_sequence(&env, 101);
+    assert_eq!(client.settle_order(&order_id), vec![&env, earliest_bid]);
+}
+
+#[test]
+fn test_settle_order_without_qualified_bids() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider_id =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::Five);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    client.place_bid(&order_id, &provider_id, &70_000, &1);
+
+    set_ledger_sequence(&env, 101);
+    assert_eq!(client.settle_order(&order_id), vec![&env]);
+
+    let order = client.get_order(&order_id);
+    assert!(matches!(order.state, OrderState::Closed));
+}
+
+#[test]
+fn test_settle_order_skips_inactive_providers() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+
+    let suspended = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let active = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    let cheapest = client.place_bid(&order_id, &suspended, &60_000, &1);
+    let bid_id = client.place_bid(&order_id, &active, &70_000, &1);
+
+    provider_registry.set_provider_status(
+        &Address::generate(&env),
+        &suspended,
+        &ProviderStatus::Suspended,
+    );
+
+    set_ledger_sequence(&env, 101);
+    assert_eq!(client.settle_order(&order_id), vec![&env, bid_id]);
+    assert!(matches!(client.get_bid(&cheapest).state, BidState::Void));
+}
+
+#[test]
+#[should_panic(expected = "Bidding for order 1 is still open")]
+fn test_settle_order_before_deadline() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    client.settle_order(&order_id);
+}
+
+#[test]
+fn test_sealed_bid_auction() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider3 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Sealed(200),
+        &order_terms(&env, 0),
+    );
+
+    let salt1 = BytesN::from_array(&env, &[1; 32]);
+    let salt2 = BytesN::from_array(&env, &[2; 32]);
+    let salt3 = BytesN::from_array(&env, &[3; 32]);
+
+    // Commit phase
+    let bid1 = client.commit_bid(
+        &order_id,
+        &provider1,
+        &1,
+        &bid_commitment(&env, 90_000, &salt1),
+    );
+    let bid2 = client.commit_bid(
+        &order_id,
+        &provider2,
+        &1,
+        &bid_commitment(&env, 60_000, &salt2),
+    );
+    client.commit_bid(
+        &order_id,
+        &provider3,
+        &1,
+        &bid_commitment(&env, 50_000, &salt3),
+    );
+
+    // Reveal phase, provider2 reveals the wrong price and provider3 never reveals
+    set_ledger_sequence(&env, 150);
+    assert!(client.reveal_bid(&bid1, &90_000, &salt1));
+    assert!(!client.reveal_bid(&bid2, &55_000, &salt2));
+
+    set_ledger_sequence(&env, 201);
+    assert_eq!(client.settle_order(&order_id), vec![&env, bid1]);
+
+    let order = client.get_order(&order_id);
+    assert!(matches!(order.state, OrderState::Complete));
+    assert_eq!(order.max_price, 100_000);
+    let leases = client.get_lease_by_order(&order_id);
+    assert_eq!(leases.get(0).unwrap().1.price, 90_000);
+}
+
+#[test]
+#[should_panic(expected = "Order with ID 1 only accepts sealed bids")]
+fn test_place_open_bid_on_sealed_order() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Sealed(200),
+        &order_terms(&env, 0),
+    );
+
+    client.place_bid(&order_id, &1, &50_000, &1);
+}
+
+#[test]
+#[should_panic(expected = "Order with ID 1 is not in its reveal phase")]
+fn test_reveal_bid_during_commit_phase() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider_id =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Sealed(200),
+        &order_terms(&env, 0),
+    );
+
+    let salt = BytesN::from_array(&env, &[7; 32]);
+    let bid_id = client.commit_bid(
+        &order_id,
+        &provider_id,
+        &1,
+        &bid_commitment(&env, 80_000, &salt),
+    );
+
+    client.reveal_bid(&bid_id, &80_000, &salt);
+}
+
+#[test]
+#[should_panic(expected = "Reveal phase for order 1 is still open")]
+fn test_settle_sealed_order_during_reveal_phase() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Sealed(200),
+        &order_terms(&env, 0),
+    );
+
+    set_ledger_sequence(&env, 150);
+    client.settle_order(&order_id);
+}
+
+#[test]
+fn test_accept_partial_fills() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &5,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    let bid_id1 = client.place_bid(&order_id, &provider1, &80_000, &2);
+    let bid_id2 = client.place_bid(&order_id, &provider2, &85_000, &3);
+
+    // First fill leaves the order active
+    client.accept_bid(&bid_id1);
+    let order = client.get_order(&order_id);
+    assert!(matches!(order.state, OrderState::Active));
+    assert_eq!(order.filled, 2);
+    assert_eq!(order.max_price, 100_000);
+
+    // Second fill completes it
+    client.accept_bid(&bid_id2);
+    let order = client.get_order(&order_id);
+    assert!(matches!(order.state, OrderState::Complete));
+    assert_eq!(order.filled, 5);
+}
+
+#[test]
+#[should_panic(expected = "Bid quantity exceeds remaining quantity of order 1")]
+fn test_accept_bid_exceeding_remaining_quantity() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &3,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    let bid_id1 = client.place_bid(&order_id, &provider1, &80_000, &2);
+    let bid_id2 = client.place_bid(&order_id, &provider2, &85_000, &2);
+
+    client.accept_bid(&bid_id1);
+    client.accept_bid(&bid_id2);
+}
+
+#[test]
+#[should_panic(expected = "Invalid bid quantity for order 1")]
+fn test_place_bid_zero_quantity() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &3,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    client.place_bid(&order_id, &1, &80_000, &0);
+}
+
+#[test]
+fn test_settle_order_partial_fills() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider3 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &4,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    let cheapest = client.place_bid(&order_id, &provider1, &60_000, &3);
+    // Too large for the remaining quantity once the cheapest bid is matched
+    client.place_bid(&order_id, &provider2, &65_000, &2);
+    let filler = client.place_bid(&order_id, &provider3, &70_000, &1);
+
+    set_ledger_sequence(&env, 101);
+    assert_eq!(client.settle_order(&order_id), vec![&env, cheapest, filler]);
+
+    let order = client.get_order(&order_id);
+    assert!(matches!(order.state, OrderState::Complete));
+    assert_eq!(order.filled, 4);
+}
+
+#[test]
+fn test_settle_order_unlocks_unfilled_quantity() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let order_id = client.create_order(
+        &tenant,
+        &token,
+        &100_000,
+        &1000,
+        &4,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    let bid_id = client.place_bid(&order_id, &provider_id, &60_000, &3);
+
+    set_ledger_sequence(&env, 101);
+    assert_eq!(client.settle_order(&order_id), vec![&env, bid_id]);
+
+    let order = client.get_order(&order_id);
+    assert!(matches!(order.state, OrderState::Closed));
+    assert_eq!(order.filled, 3);
+
+    // only the matched lease's price stays locked
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 180_000);
+    assert_eq!(balance.unlocked_balance, 9_820_000);
+}
+
+#[test]
+fn test_settle_order_releases_skipped_bonds() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    client.set_bid_bond(&10, &20);
+
+    let provider_id1 =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider_id2 =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    order_book.fund_provider(provider_id1);
+    let provider2 = order_book.fund_provider(provider_id2);
+
+    let order_id = client.create_order(
+        &tenant,
+        &token,
+        &100_000,
+        &1000,
+        &4,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    let cheapest = client.place_bid(&order_id, &provider_id1, &60_000, &3);
+    // Too large for the remaining quantity once the cheapest bid is matched
+    let skipped = client.place_bid(&order_id, &provider_id2, &65_000, &2);
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider2);
+    assert_eq!(balance.locked_balance, 13_000);
+
+    set_ledger_sequence(&env, 101);
+    assert_eq!(client.settle_order(&order_id), vec![&env, cheapest]);
+
+    let bid = client.get_bid(&skipped);
+    assert!(matches!(bid.state, BidState::Void));
+    assert_eq!(bid.bond, 0);
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider2);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 1_000_000);
+}
+
+#[test]
+fn test_failed_reveal_releases_bond() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let provider_registry = &order_book.provider_registry;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    client.set_bid_bond(&10, &20);
+
+    let provider_id =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider = order_book.fund_provider(provider_id);
+
+    let order_id = client.create_order(
+        &tenant,
+        &token,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Sealed(200),
+        &order_terms(&env, 0),
+    );
+
+    let salt = BytesN::from_array(&env, &[1; 32]);
+    let bid_id = client.commit_bid(
+        &order_id,
+        &provider_id,
+        &1,
+        &bid_commitment(&env, 60_000, &salt),
+    );
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider);
+    assert!(balance.locked_balance > 0);
+
+    set_ledger_sequence(&env, 150);
+    assert!(!client.reveal_bid(&bid_id, &55_000, &salt));
+
+    let bid = client.get_bid(&bid_id);
+    assert!(matches!(bid.state, BidState::Void));
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 1_000_000);
+}
+
+#[test]
+fn test_lease_queries() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let other_tenant = order_book.create_tenant(&env);
+
+    set_ledger_sequence(&env, 10);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &3,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    let other_order_id = client.create_order(
+        &other_tenant,
+        &order_book.token.address,
+        &100_000,
+        &500,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:2,memory:4G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    let bid_id1 = client.place_bid(&order_id, &provider1, &80_000, &1);
+    let bid_id2 = client.place_bid(&order_id, &provider2, &85_000, &2);
+    let bid_id3 = client.place_bid(&other_order_id, &provider1, &90_000, &1);
+
+    let lease_id1 = client.accept_bid(&bid_id1);
+    let lease_id2 = client.accept_bid(&bid_id2);
+    let lease_id3 = client.accept_bid(&bid_id3);
+    assert_eq!((lease_id1, lease_id2, lease_id3), (1, 2, 3));
+
+    let lease = client.get_lease(&lease_id2);
+    assert_eq!(lease.order_id, order_id);
+    assert_eq!(lease.bid_id, bid_id2);
+    assert_eq!(lease.provider_id, provider2);
+    assert_eq!(lease.tenant, tenant);
+    assert_eq!(lease.start_block, 10);
+    assert_eq!(lease.end_block, 1010);
+
+    // Leases by order
+    let order_leases = client.get_lease_by_order(&order_id);
+    assert_eq!(order_leases.len(), 2);
+    assert_eq!(order_leases.get(0).unwrap().0, lease_id1);
+    assert_eq!(order_leases.get(1).unwrap().0, lease_id2);
+
+    // Leases by tenant, paginated
+    let tenant_leases = client.list_leases(&LeaseOwner::Tenant(tenant.clone()), &0, &1);
+    assert_eq!(tenant_leases.len(), 1);
+    assert_eq!(tenant_leases.get(0).unwrap().0, lease_id1);
+    let tenant_leases = client.list_leases(&LeaseOwner::Tenant(tenant.clone()), &1, &10);
+    assert_eq!(tenant_leases.len(), 1);
+    assert_eq!(tenant_leases.get(0).unwrap().0, lease_id2);
+    assert_eq!(
+        client
+            .list_leases(&LeaseOwner::Tenant(tenant), &2, &10)
+            .len(),
+        0
+    );
+
+    // Leases by provider
+    let provider_leases = client.list_leases(&LeaseOwner::Provider(provider1), &0, &10);
+    assert_eq!(provider_leases.len(), 2);
+    assert_eq!(provider_leases.get(0).unwrap().0, lease_id1);
+    assert_eq!(provider_leases.get(1).unwrap().0, lease_id3);
+}
+
+#[test]
+#[should_panic(expected = "Lease with ID 999 not found")]
+fn test_get_nonexistent_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+
+    client.get_lease(&999);
+}
+
+#[test]
+fn test_order_escrow_locks() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider_id =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let token = order_book.token.address.clone();
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &3,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    // Max price for the full quantity is locked
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 300_000);
+    assert_eq!(balance.unlocked_balance, 9_700_000);
+
+    // Matching releases the difference to the bid price
+    let bid_id = client.place_bid(&order_id, &provider_id, &80_000, &1);
+    client.accept_bid(&bid_id);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 280_000);
+
+    // Closing releases the unfilled quantity
+    client.update_order_to_closed(&order_id);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 80_000);
+    assert_eq!(balance.unlocked_balance, 9_920_000);
+}
+
+#[test]
+fn test_lease_lifecycle() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    set_ledger_sequence(&env, 10);
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Initiate));
+    assert_eq!(lease.price, 80_000);
+
+    // The lease term starts once the provider activates it
+    set_ledger_sequence(&env, 20);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Active));
+    assert_eq!(lease.start_block, 20);
+    assert_eq!(lease.end_block, 120);
+    assert_eq!(lease.container, Some(String::from_str(&env, "container-1")));
+
+    // Anyone can complete the lease once it has ended
+    set_ledger_sequence(&env, 121);
+    client.complete_lease(&lease_id);
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Completed));
+
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 80_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 9_920_000);
+}
+
+#[test]
+#[should_panic(expected = "Lease with ID 1 has not ended")]
+fn test_complete_lease_before_end() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+
+    set_ledger_sequence(&env, 50);
+    client.complete_lease(&lease_id);
+}
+
+#[test]
+fn test_cancel_initiated_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.cancel_lease(&tenant, &lease_id);
+
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Canceled));
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 10_000_000);
+}
+
+#[test]
+fn test_provider_cancels_active_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let provider = order_book.provider_registry.get_provider(&provider_id);
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    client.cancel_lease(&provider.address, &lease_id);
+
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Canceled));
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.unlocked_balance, 10_000_000);
+}
+
+#[test]
+#[should_panic(expected = "Not allowed to cancel lease with ID 1")]
+fn test_tenant_cancels_active_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    client.cancel_lease(&tenant, &lease_id);
+}
+
+#[test]
+fn test_extend_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+
+    // Extending by half the term costs half the price
+    client.extend_lease(&lease_id, &50);
+    let lease = client.get_lease(&lease_id);
+    assert_eq!(lease.end_block, 150);
+    assert_eq!(lease.price, 120_000);
+
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 120_000);
+
+    set_ledger_sequence(&env, 151);
+    client.complete_lease(&lease_id);
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 120_000);
+}
+
+#[test]
+fn test_extend_cheap_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    // Less than one unit per block
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 500, 1000);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+
+    for _ in 0..100 {
+        client.extend_lease(&lease_id, &1);
+    }
+    let lease = client.get_lease(&lease_id);
+    assert_eq!(lease.end_block, 1100);
+    assert_eq!(lease.price, 600);
+}
+
+#[test]
+fn test_auto_renew_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    client.set_auto_renew(&lease_id, &100, &2);
+
+    // First two attempts to complete renew the lease instead
+    set_ledger_sequence(&env, 101);
+    client.complete_lease(&lease_id);
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Active));
+    assert_eq!(lease.end_block, 200);
+    assert_eq!(lease.price, 160_000);
+
+    set_ledger_sequence(&env, 201);
+    client.complete_lease(&lease_id);
+    let lease = client.get_lease(&lease_id);
+    assert_eq!(lease.end_block, 300);
+    assert_eq!(lease.price, 240_000);
+
+    // Renewal cap reached, the lease completes
+    set_ledger_sequence(&env, 301);
+    client.complete_lease(&lease_id);
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Completed));
+
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 240_000);
+}
+
+#[test]
+fn test_auto_renew_without_funds() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    client.set_auto_renew(&lease_id, &100, &5);
+
+    // Tenant withdraws everything that is not locked
+    order_book.escrow.withdraw(&token, &tenant, &9_920_000);
+
+    set_ledger_sequence(&env, 101);
+    client.complete_lease(&lease_id);
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Completed));
+    assert_eq!(lease.price, 80_000);
+}
+
+#[test]
+fn test_terminate_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+
+    // Terminate a quarter of the way through
+    set_ledger_sequence(&env, 25);
+    client.terminate_lease(&lease_id);
+
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Canceled));
+    assert_eq!(lease.end_block, 25);
+    assert_eq!(lease.price, 20_000);
+
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 20_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 9_980_000);
+}
+
+#[test]
+fn test_terminate_lease_minimum_billing() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 50),
+    );
+    let bid_id = client.place_bid(&order_id, &provider_id, &80_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+
+    // Only ten blocks served, but half the term is billed
+    set_ledger_sequence(&env, 10);
+    client.terminate_lease(&lease_id);
+
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 40_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.unlocked_balance, 9_960_000);
+}
+
+#[test]
+#[should_panic(expected = "Lease with ID 1 is not active")]
+fn test_terminate_initiated_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.terminate_lease(&lease_id);
+}
+
+#[test]
+fn test_withdraw_claimed_lease_earnings() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let provider = order_book
+        .provider_registry
+        .get_provider(&provider_id)
+        .address;
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let start_block = client.get_lease(&lease_id).start_block;
+
+    set_ledger_sequence(&env, start_block + 25);
+    assert_eq!(client.claim_lease_earnings(&lease_id), 20_000);
+
+    // Claimed earnings can be withdrawn from escrow right away
+    order_book
+        .escrow
+        .withdraw_provider_earnings(&provider, &token);
+    assert_eq!(order_book.token.balance(&provider), 20_000);
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.withdrawn, 20_000);
+    assert_eq!(earnings.balance, 0);
+}
+
+#[test]
+fn test_claim_lease_earnings() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+
+    // Nothing accrues before activation
+    set_ledger_sequence(&env, 10);
+    assert_eq!(client.get_claimable_earnings(&lease_id), 0);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+
+    set_ledger_sequence(&env, 35);
+    assert_eq!(client.get_claimable_earnings(&lease_id), 20_000);
+    assert_eq!(client.claim_lease_earnings(&lease_id), 20_000);
+    assert_eq!(client.get_claimable_earnings(&lease_id), 0);
+
+    set_ledger_sequence(&env, 60);
+    assert_eq!(client.claim_lease_earnings(&lease_id), 20_000);
+
+    let lease = client.get_lease(&lease_id);
+    assert_eq!(lease.claimed, 40_000);
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 40_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 40_000);
+
+    // Nothing more accrues after the end block, completion pays the rest
+    set_ledger_sequence(&env, 200);
+    assert_eq!(client.get_claimable_earnings(&lease_id), 40_000);
+    client.complete_lease(&lease_id);
+
+    let lease = client.get_lease(&lease_id);
+    assert_eq!(lease.claimed, 80_000);
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 80_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+}
+
+#[test]
+fn test_terminate_lease_after_claim() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 80_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+
+    set_ledger_sequence(&env, 25);
+    client.claim_lease_earnings(&lease_id);
+
+    set_ledger_sequence(&env, 50);
+    client.terminate_lease(&lease_id);
+
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 40_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 9_960_000);
+}
+
+#[test]
+fn test_create_order_with_resource_spec() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Resources(resource_spec(&env)),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    let order = client.get_order(&order_id);
+    match order.spec.spec {
+        OrderSpec::Resources(resources) => {
+            assert_eq!(resources, resource_spec(&env));
+            assert_eq!(resources.cpu_millis, 4000);
+            assert_eq!(resources.ports.len(), 2);
+        }
+        OrderSpec::Text(_) => panic!("expected a resource specification"),
+    }
+}
+
+#[test]
+#[should_panic(expected = "Resource quantities must be positive")]
+fn test_create_order_with_zero_resources() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let mut resources = resource_spec(&env);
+    resources.memory_mb = 0;
+
+    client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Resources(resources),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invalid port 70000")]
+fn test_create_order_with_invalid_port() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let mut resources = resource_spec(&env);
+    resources.ports = vec![&env, 8080, 70000];
+
+    client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Resources(resources),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+}
+
+#[test]
+fn test_list_orders_with_filter() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let other_tenant = order_book.create_tenant(&env);
+
+    for (owner, max_price) in [
+        (&tenant, 50_000),
+        (&other_tenant, 60_000),
+        (&tenant, 70_000),
+        (&tenant, 80_000),
+        (&other_tenant, 90_000),
+    ] {
+        client.create_order(
+            owner,
+            &order_book.token.address,
+            &max_price,
+            &500,
+            &1,
+            &OrderSpec::Text(String::from_str(&env, "cpu:2,memory:4G")),
+            &vec![&env, TrustLevel::One],
+            &100,
+            &AuctionMode::Open,
+            &order_terms(&env, 0),
+        );
+    }
+    client.update_order_to_closed(&3);
+
+    // Active orders come from the active index, with their IDs
+    let mut filter = any_order(&env);
+    filter.states = vec![&env, OrderState::Active];
+    let orders = client.list_orders(&filter, &0, &10);
+    assert_eq!(orders.len(), 4);
+    assert_eq!(orders.get(2).unwrap().0, 4);
+
+    // Paginate with the last returned ID as cursor
+    let page = client.list_orders(&filter, &0, &2);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(1).unwrap().0, 2);
+    let page = client.list_orders(&filter, &2, &2);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get(0).unwrap().0, 4);
+    assert_eq!(page.get(1).unwrap().0, 5);
+
+    // Closed orders
+    filter.states = vec![&env, OrderState::Closed];
+    let orders = client.list_orders(&filter, &0, &10);
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders.get(0).unwrap().0, 3);
+
+    // Tenant and price range
+    let mut filter = any_order(&env);
+    filter.tenant = Some(tenant.clone());
+    filter.min_price = Some(60_000);
+    filter.max_price = Some(80_000);
+    let orders = client.list_orders(&filter, &0, &10);
+    assert_eq!(orders.len(), 2);
+    assert_eq!(orders.get(0).unwrap().0, 3);
+    assert_eq!(orders.get(1).unwrap().0, 4);
+
+    // Token
+    let mut filter = any_order(&env);
+    filter.token = Some(Address::generate(&env));
+    assert_eq!(client.list_orders(&filter, &0, &10).len(), 0);
+    filter.token = Some(order_book.token.address.clone());
+    assert_eq!(client.list_orders(&filter, &0, &10).len(), 5);
+}
+
+#[test]
+fn test_list_bids() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider3 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let mut order_ids = Vec::new(&env);
+    for _ in 0..2 {
+        order_ids.push_back(client.create_order(
+            &tenant,
+            &order_book.token.address,
+            &100_000,
+            &500,
+            &3,
+            &OrderSpec::Text(String::from_str(&env, "cpu:2,memory:4G")),
+            &vec![&env, TrustLevel::One],
+            &100,
+            &AuctionMode::Open,
+            &order_terms(&env, 0),
+        ));
+    }
+    let order_id1 = order_ids.get(0).unwrap();
+    let order_id2 = order_ids.get(1).unwrap();
+
+    let bid_id1 = client.place_bid(&order_id1, &provider1, &90_000, &1);
+    let bid_id2 = client.place_bid(&order_id1, &provider2, &80_000, &2);
+    let bid_id3 = client.place_bid(&order_id2, &provider1, &70_000, &3);
+    let bid_id4 = client.place_bid(&order_id1, &provider3, &75_000, &1);
+
+    let bid = client.get_bid(&bid_id2);
+    assert_eq!(bid.order_id, order_id1);
+    assert_eq!(bid.provider, provider2);
+    assert_eq!(bid.bid_price, 80_000);
+    assert_eq!(bid.quantity, 2);
+    assert!(matches!(bid.state, BidState::Active));
+
+    // Bids by order, paginated
+    let bids = client.list_bids_for_order(&order_id1, &0, &2);
+    assert_eq!(bids.len(), 2);
+    assert_eq!(bids.get(0).unwrap().0, bid_id1);
+    assert_eq!(bids.get(1).unwrap().0, bid_id2);
+    let bids = client.list_bids_for_order(&order_id1, &2, &2);
+    assert_eq!(bids.len(), 1);
+    assert_eq!(bids.get(0).unwrap().0, bid_id4);
+
+    // Bids by provider
+    let bids = client.list_bids_for_provider(&provider1, &0, &10);
+    assert_eq!(bids.len(), 2);
+    assert_eq!(bids.get(0).unwrap().0, bid_id1);
+    assert_eq!(bids.get(1).unwrap().0, bid_id3);
+    assert_eq!(client.list_bids_for_provider(&99, &0, &10).len(), 0);
+
+    // Matching is reflected in the listing
+    client.accept_bid(&bid_id2);
+    let bids = client.list_bids_for_order(&order_id1, &1, &1);
+    assert!(matches!(bids.get(0).unwrap().1.state, BidState::Matched));
+}
+
+#[test]
+#[should_panic(expected = "Bid with ID 999 not found")]
+fn test_get_nonexistent_bid() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+
+    order_book.client.get_bid(&999);
+}
+
+#[test]
+fn test_order_and_bid_events() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::Five,
+    );
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &2,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::Five],
+        &(env.ledger().sequence() + 100),
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    assert!(has_event(
+        &env,
+        &client.address,
+        (Symbol::new(&env, "order_created"), order_id, tenant.clone()).into_val(&env),
+        (100_000u128, 2u64, env.ledger().sequence() + 100).into_val(&env),
+    ));
+
+    let bid_id = client.place_bid(&order_id, &provider_id, &90_000, &1);
+    assert!(has_event(
+        &env,
+        &client.address,
+        (Symbol::new(&env, "bid_placed"), order_id, provider_id).into_val(&env),
+        (bid_id, 90_000u128, 1u64).into_val(&env),
+    ));
+
+    client.update_bid(&bid_id, &85_000);
+    assert!(has_event(
+        &env,
+        &client.address,
+        (Symbol::new(&env, "bid_revised"), order_id, provider_id).into_val(&env),
+        (bid_id, 85_000u128, 1u64).into_val(&env),
+    ));
+    assert_eq!(client.get_bid(&bid_id).bid_price, 85_000);
+
+    client.cancel_bid(&bid_id);
+    assert!(has_event(
+        &env,
+        &client.address,
+        (Symbol::new(&env, "bid_canceled"), order_id, provider_id).into_val(&env),
+        (bid_id, 85_000u128, 1u64).into_val(&env),
+    ));
+    assert!(matches!(client.get_bid(&bid_id).state, BidState::Canceled));
+
+    let bid_id = client.place_bid(&order_id, &provider_id, &80_000, &1);
+    client.accept_bid(&bid_id);
+    assert!(has_event(
+        &env,
+        &client.address,
+        (Symbol::new(&env, "bid_matched"), order_id, provider_id).into_val(&env),
+        (bid_id, 80_000u128, 1u64).into_val(&env),
+    ));
+
+    client.update_order_to_closed(&order_id);
+    assert!(has_event(
+        &env,
+        &client.address,
+        (Symbol::new(&env, "order_closed"), order_id, tenant.clone()).into_val(&env),
+        1u64.into_val(&env),
+    ));
+
+    // Filling the whole quantity completes the order
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::Five],
+        &(env.ledger().sequence() + 100),
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    let bid_id = client.place_bid(&order_id, &provider_id, &80_000, &1);
+    client.accept_bid(&bid_id);
+    assert!(has_event(
+        &env,
+        &client.address,
+        (Symbol::new(&env, "order_completed"), order_id, tenant).into_val(&env),
+        1u64.into_val(&env),
+    ));
+}
+
+#[test]
+fn test_lease_events() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    // the first order of the book
+    let order_id = 1u64;
+    let topics = |name: &str| -> Vec<Val> {
+        (
+            Symbol::new(&env, name),
+            order_id,
+            provider_id,
+            tenant.clone(),
+        )
+            .into_val(&env)
+    };
+    assert!(has_event(
+        &env,
+        &client.address,
+        topics("lease_initiated"),
+        (lease_id, 50_000u128).into_val(&env),
+    ));
+
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    assert!(has_event(
+        &env,
+        &client.address,
+        topics("lease_activated"),
+        (lease_id, 50_000u128).into_val(&env),
+    ));
+
+    set_ledger_sequence(&env, env.ledger().sequence() + 101);
+    client.complete_lease(&lease_id);
+    assert!(has_event(
+        &env,
+        &client.address,
+        topics("lease_completed"),
+        (lease_id, 50_000u128).into_val(&env),
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Bid with ID 1 can not be canceled")]
+fn test_cancel_matched_bid() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.cancel_bid(&client.get_lease(&lease_id).bid_id);
+}
+
+#[test]
+fn test_accepted_tokens() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let token = order_book.token.address.clone();
+    let other_token = Address::generate(&env);
+
+    assert_eq!(client.get_accepted_tokens(), vec![&env, token.clone()]);
+
+    client.add_accepted_token(&other_token);
+    client.add_accepted_token(&other_token);
+    assert_eq!(
+        client.get_accepted_tokens(),
+        vec![&env, token.clone(), other_token.clone()]
+    );
+
+    client.remove_accepted_token(&token);
+    assert_eq!(client.get_accepted_tokens(), vec![&env, other_token]);
+}
+
+#[test]
+#[should_panic(expected = "Payment token is not accepted")]
+fn test_create_order_with_unaccepted_token() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+
+    order_book.client.create_order(
+        &tenant,
+        &Address::generate(&env),
+        &100_000,
+        &1000,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+}
+
+#[test]
+fn test_order_settles_in_its_token() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    // Fund the tenant in a second accepted token
+    let other_sac = env.register_stellar_asset_contract_v2(Address::generate(&env));
+    let other_token = other_sac.address();
+    TokenAdminClient::new(&env, &other_token).mint(&tenant, &1_000_000);
+    order_book.escrow.deposit(&other_token, &tenant, &1_000_000);
+    client.add_accepted_token(&other_token);
+
+    let order_id = client.create_order(
+        &tenant,
+        &other_token,
+        &100_000,
+        &100,
+        &2,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    assert_eq!(client.get_order(&order_id).token, other_token);
+
+    let balance = order_book.escrow.get_tenant_balance(&other_token, &tenant);
+    assert_eq!(balance.locked_balance, 200_000);
+
+    // Orders can be filtered by token
+    let mut filter = any_order(&env);
+    filter.token = Some(other_token.clone());
+    assert_eq!(client.list_orders(&filter, &0, &10).len(), 1);
+    filter.token = Some(token.clone());
+    assert_eq!(client.list_orders(&filter, &0, &10).len(), 0);
+
+    let bid_id = client.place_bid(&order_id, &provider_id, &60_000, &2);
+    let lease_id = client.accept_bid(&bid_id);
+    assert_eq!(client.get_lease(&lease_id).token, other_token);
+
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    set_ledger_sequence(&env, env.ledger().sequence() + 101);
+    client.complete_lease(&lease_id);
+
+    // The provider is paid in the order's token only
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &other_token);
+    assert_eq!(earnings.earned, 120_000);
+    assert!(order_book
+        .escrow
+        .try_get_provider_earnings(&provider_id, &token)
+        .is_err());
+
+    let balance = order_book.escrow.get_tenant_balance(&other_token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 880_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.unlocked_balance, 10_000_000);
+}
+
+// Helper function to create a Dutch order falling from 100_000 to 40_000 over
+// 60 ledgers
+fn create_dutch_order(env: &Env, order_book: &OrderBookTest, tenant: &Address) -> u64 {
+    order_book.client.create_order(
+        tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &2,
+        &OrderSpec::Text(String::from_str(env, "cpu:4,memory:8G")),
+        &vec![env, TrustLevel::One, TrustLevel::Two],
+        &(env.ledger().sequence() + 100),
+        &AuctionMode::Dutch(DutchAuction {
+            floor_price: 40_000,
+            decay_blocks: 60,
+        }),
+        &order_terms(env, 0),
+    )
+}
+
+#[test]
+fn test_dutch_auction_take_order() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::Two,
+    );
+
+    let start = env.ledger().sequence();
+    let order_id = create_dutch_order(&env, &order_book, &tenant);
+    assert_eq!(client.get_current_price(&order_id), 100_000);
+
+    set_ledger_sequence(&env, start + 30);
+    assert_eq!(client.get_current_price(&order_id), 70_000);
+
+    // The first taker gets the current price
+    let lease_id = client.take_order(&order_id, &provider_id, &1);
+    let lease = client.get_lease(&lease_id);
+    assert_eq!(lease.price, 70_000);
+    assert_eq!(lease.provider_id, provider_id);
+    assert!(matches!(
+        client.get_bid(&lease.bid_id).state,
+        BidState::Matched
+    ));
+
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 170_000);
+
+    // The price bottoms out at the floor
+    set_ledger_sequence(&env, start + 90);
+    assert_eq!(client.get_current_price(&order_id), 40_000);
+    let lease_id = client.take_order(&order_id, &provider_id, &1);
+    assert_eq!(client.get_lease(&lease_id).price, 40_000);
+    assert!(matches!(
+        client.get_order(&order_id).state,
+        OrderState::Complete
+    ));
+
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 110_000);
+}
+
+#[test]
+#[should_panic(expected = "Provider 1 is not eligible for order 1")]
+fn test_take_order_ineligible_provider() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::Four,
+    );
+
+    let order_id = create_dutch_order(&env, &order_book, &tenant);
+    order_book.client.take_order(&order_id, &provider_id, &1);
+}
+
+#[test]
+#[should_panic(expected = "Provider 1 is not active")]
+fn test_take_order_suspended_provider() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    order_book.provider_registry.set_provider_status(
+        &Address::generate(&env),
+        &provider_id,
+        &ProviderStatus::Suspended,
+    );
+
+    let order_id = create_dutch_order(&env, &order_book, &tenant);
+    order_book.client.take_order(&order_id, &provider_id, &1);
+}
+
+#[test]
+#[should_panic(expected = "Order with ID 1 can only be taken")]
+fn test_place_bid_on_dutch_order() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = create_dutch_order(&env, &order_book, &tenant);
+    order_book.client.place_bid(&order_id, &1, &90_000, &1);
+}
+
+#[test]
+fn test_amend_order() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider1 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let provider2 = register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+    let token = order_book.token.address.clone();
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &50_000,
+        &100,
+        &3,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    let bid_id = client.place_bid(&order_id, &provider1, &45_000, &1);
+    client.accept_bid(&bid_id);
+
+    client.amend_order(
+        &order_id,
+        &80_000,
+        &200,
+        &vec![&env, TrustLevel::One, TrustLevel::Three],
+    );
+    assert!(has_event(
+        &env,
+        &client.address,
+        (Symbol::new(&env, "order_amended"), order_id, tenant.clone()).into_val(&env),
+        (
+            80_000u128,
+            200u32,
+            vec![&env, TrustLevel::One, TrustLevel::Three]
+        )
+            .into_val(&env),
+    ));
+
+    let order = client.get_order(&order_id);
+    assert_eq!(order.max_price, 80_000);
+    assert_eq!(order.bid_deadline, 200);
+    assert_eq!(order.spec.trust_levels.len(), 2);
+
+    // Only the unfilled quantity is topped up
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 45_000 + 2 * 80_000);
+
+    // Bids up to the new max price are accepted past the old deadline
+    set_ledger_sequence(&env, 150);
+    client.place_bid(&order_id, &provider2, &75_000, &2);
+}
+
+#[test]
+#[should_panic(expected = "Max price can not be lowered")]
+fn test_amend_order_lower_price() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &50_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    client.amend_order(&order_id, &40_000, &100, &vec![&env, TrustLevel::One]);
+}
+
+#[test]
+#[should_panic(expected = "Allowed trust levels can only be widened")]
+fn test_amend_order_narrow_trust_levels() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &50_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One, TrustLevel::Two],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+    client.amend_order(&order_id, &50_000, &100, &vec![&env, TrustLevel::Two]);
+}
+
+#[test]
+fn test_bid_bond_lifecycle() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    client.set_bid_bond(&10, &20);
+    let bid_bond = client.get_bid_bond();
+    assert_eq!(bid_bond.percentage, 10);
+    assert_eq!(bid_bond.activation_blocks, 20);
+
+    let provider_id1 = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let provider_id2 = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let provider_id3 = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let provider1 = order_book.fund_provider(provider_id1);
+    let provider2 = order_book.fund_provider(provider_id2);
+    let provider3 = order_book.fund_provider(provider_id3);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &order_terms(&env, 0),
+    );
+
+    let bid_id1 = client.place_bid(&order_id, &provider_id1, &80_000, &1);
+    let bid_id2 = client.place_bid(&order_id, &provider_id2, &90_000, &1);
+    let bid_id3 = client.place_bid(&order_id, &provider_id3, &95_000, &1);
+    assert_eq!(client.get_bid(&bid_id1).bond, 8_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider2);
+    assert_eq!(balance.locked_balance, 9_000);
+
+    // Revising re-posts the bond at the new price
+    client.update_bid(&bid_id2, &70_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider2);
+    assert_eq!(balance.locked_balance, 7_000);
+    assert_eq!(balance.unlocked_balance, 993_000);
+
+    // Canceling returns the bond
+    client.cancel_bid(&bid_id3);
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider3);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 1_000_000);
+
+    // Losing bids get their bond back once the order is filled
+    let lease_id = client.accept_bid(&bid_id1);
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider2);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 1_000_000);
+
+    // The winner's bond is held until the lease is activated
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider1);
+    assert_eq!(balance.locked_balance, 8_000);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider1);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 1_000_000);
+}
+
+#[test]
+fn test_forfeit_bid_bond() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    client.set_bid_bond(&10, &20);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let provider = order_book.fund_provider(provider_id);
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+
+    set_ledger_sequence(&env, env.ledger().sequence() + 21);
+    client.forfeit_bid_bond(&lease_id);
+
+    assert!(matches!(
+        client.get_lease(&lease_id).state,
+        LeaseState::Canceled
+    ));
+
+    // The tenant is refunded and receives the bond
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 10_005_000);
+
+    let balance = order_book.escrow.get_tenant_balance(&token, &provider);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 995_000);
+}
+
+#[test]
+#[should_panic(expected = "Activation window of lease 1 is still open")]
+fn test_forfeit_bid_bond_window_open() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    client.set_bid_bond(&10, &20);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    order_book.fund_provider(provider_id);
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+
+    set_ledger_sequence(&env, env.ledger().sequence() + 20);
+    client.forfeit_bid_bond(&lease_id);
+}
+
+// Helper function to declare room for two units of `resource_spec`
+fn declare_capacity(order_book: &OrderBookTest, provider_id: u64) {
+    order_book.provider_registry.set_capacity(
+        &provider_id,
+        &Capacity {
+            cpu_millis: 8000,
+            memory_mb: 16384,
+            storage_gb: 200,
+            gpus: 2,
+        },
+    );
+}
+
+// Helper function to create a resource order with the given quantity
+fn create_resource_order(
+    env: &Env,
+    order_book: &OrderBookTest,
+    tenant: &Address,
+    quantity: u64,
+) -> u64 {
+    order_book.client.create_order(
+        tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &quantity,
+        &OrderSpec::Resources(resource_spec(env)),
+        &vec![env, TrustLevel::One],
+        &(env.ledger().sequence() + 100),
+        &AuctionMode::Open,
+        &order_terms(env, 0),
+    )
+}
+
+#[test]
+fn test_provider_capacity() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    declare_capacity(&order_book, provider_id);
+
+    let order_id = create_resource_order(&env, &order_book, &tenant, 2);
+    let bid_id = client.place_bid(&order_id, &provider_id, &80_000, &2);
+    let lease_id = client.accept_bid(&bid_id);
+
+    assert_eq!(
+        client.get_committed_capacity(&provider_id),
+        Capacity {
+            cpu_millis: 8000,
+            memory_mb: 16384,
+            storage_gb: 200,
+            gpus: 2,
+        }
+    );
+    assert_eq!(client.get_free_capacity(&provider_id), Capacity::zero());
+
+    // No room for another lease until this one is closed
+    let order_id = create_resource_order(&env, &order_book, &tenant, 1);
+    assert!(client
+        .try_place_bid(&order_id, &provider_id, &80_000, &1)
+        .is_err());
+
+    client.cancel_lease(&tenant, &lease_id);
+    assert_eq!(
+        client.get_committed_capacity(&provider_id),
+        Capacity::zero()
+    );
+
+    let bid_id = client.place_bid(&order_id, &provider_id, &80_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+    assert_eq!(
+        client.get_free_capacity(&provider_id),
+        Capacity {
+            cpu_millis: 4000,
+            memory_mb: 8192,
+            storage_gb: 100,
+            gpus: 1,
+        }
+    );
+
+    // Completing a lease releases its capacity too
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    set_ledger_sequence(&env, env.ledger().sequence() + 101);
+    client.complete_lease(&lease_id);
+    assert_eq!(
+        client.get_committed_capacity(&provider_id),
+        Capacity::zero()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Provider 1 does not have capacity for order 1")]
+fn test_place_bid_over_capacity() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    declare_capacity(&order_book, provider_id);
+
+    let order_id = create_resource_order(&env, &order_book, &tenant, 3);
+    order_book
+        .client
+        .place_bid(&order_id, &provider_id, &80_000, &3);
+}
+
+#[test]
+fn test_settle_order_skips_providers_without_capacity() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id1 = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let provider_id2 = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    declare_capacity(&order_book, provider_id1);
+    declare_capacity(&order_book, provider_id2);
+
+    // Provider 1 bids on two orders but can only run one of them
+    let order_id1 = create_resource_order(&env, &order_book, &tenant, 2);
+    let order_id2 = create_resource_order(&env, &order_book, &tenant, 2);
+    client.place_bid(&order_id1, &provider_id1, &50_000, &2);
+    client.place_bid(&order_id2, &provider_id1, &50_000, &2);
+    let bid_id = client.place_bid(&order_id2, &provider_id2, &60_000, &2);
+
+    set_ledger_sequence(&env, env.ledger().sequence() + 101);
+    client.settle_order(&order_id1);
+    assert_eq!(client.settle_order(&order_id2), vec![&env, bid_id]);
+}
+
+#[test]
+fn test_protocol_fee_on_lease_payments() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    order_book.escrow.set_protocol_fee(&100);
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+
+    set_ledger_sequence(&env, env.ledger().sequence() + 40);
+    client.claim_lease_earnings(&lease_id);
+    set_ledger_sequence(&env, env.ledger().sequence() + 61);
+    client.complete_lease(&lease_id);
+
+    // 1% of every payout is kept for the treasury
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 49_500);
+    assert_eq!(order_book.escrow.get_protocol_fees(&token).collected, 500);
+    assert_eq!(client.get_lease(&lease_id).claimed, 50_000);
+}
+
+#[test]
+fn test_protocol_fee_on_per_block_claims() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    order_book.escrow.set_protocol_fee(&100);
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 5_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let start_block = client.get_lease(&lease_id).start_block;
+
+    // Claiming every block pays 50 at a time, half a unit of fee each
+    for block in 1..=100 {
+        set_ledger_sequence(&env, start_block + block);
+        assert_eq!(client.claim_lease_earnings(&lease_id), 50);
+    }
+
+    assert_eq!(order_book.escrow.get_protocol_fees(&token).collected, 50);
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 4_950);
+}
+
+// Helper function to build a 99.9% uptime SLA with two penalty tiers
+fn sla_terms(env: &Env) -> SlaTerms {
+    SlaTerms {
+        min_uptime_bps: 9_990,
+        max_latency_ms: 250,
+        downtime_budget: 10,
+        penalties: vec![
+            env,
+            SlaPenalty {
+                downtime_blocks: 5,
+                refund_bps: 1_000,
+            },
+            SlaPenalty {
+                downtime_blocks: 20,
+                refund_bps: 5_000,
+            },
+        ],
+    }
+}
+
+#[test]
+fn test_sla_terms_copied_to_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_registry = &order_book.provider_registry;
+    let provider_id =
+        register_provider(&env, provider_registry, provider_registry::TrustLevel::One);
+
+    let order_id = client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &OrderTerms {
+            min_billing_blocks: 10,
+            sla: sla_terms(&env),
+            class: OrderClass::Regular,
+        },
+    );
+    assert_eq!(client.get_order(&order_id).terms.sla, sla_terms(&env));
+
+    let bid_id = client.place_bid(&order_id, &provider_id, &80_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+    assert_eq!(client.get_lease(&lease_id).sla, sla_terms(&env));
+}
+
+#[test]
+#[should_panic(expected = "Penalties must escalate with downtime")]
+fn test_create_order_with_unordered_penalties() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+
+    let mut sla = sla_terms(&env);
+    let first = sla.penalties.pop_front_unchecked();
+    sla.penalties.push_back(first);
+
+    order_book.client.create_order(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &100,
+        &AuctionMode::Open,
+        &OrderTerms {
+            min_billing_blocks: 0,
+            sla,
+            class: OrderClass::Regular,
+        },
+    );
+}
+
+#[test]
+fn test_undisputed_lease_finalizes_after_window() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    client.set_dispute_window(&20);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let end_block = client.get_lease(&lease_id).end_block;
+
+    set_ledger_sequence(&env, end_block + 1);
+    client.complete_lease(&lease_id);
+    assert!(matches!(
+        client.get_lease(&lease_id).state,
+        LeaseState::Ended
+    ));
+    assert!(client.try_complete_lease(&lease_id).is_err());
+
+    // The payout is still held in escrow
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 50_000);
+
+    set_ledger_sequence(&env, end_block + 21);
+    client.complete_lease(&lease_id);
+    assert!(matches!(
+        client.get_lease(&lease_id).state,
+        LeaseState::Completed
+    ));
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 50_000);
+}
+
+#[test]
+fn test_dispute_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let arbiter = Address::generate(&env);
+
+    client.set_dispute_window(&20);
+    client.set_arbiter(&arbiter);
+    assert_eq!(client.get_arbiter(), arbiter);
+
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let end_block = client.get_lease(&lease_id).end_block;
+
+    set_ledger_sequence(&env, end_block - 50);
+    client.claim_lease_earnings(&lease_id);
+    set_ledger_sequence(&env, end_block + 1);
+    client.complete_lease(&lease_id);
+
+    let evidence = BytesN::from_array(&env, &[7; 32]);
+    client.open_dispute(&lease_id, &evidence);
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Disputed));
+    assert_eq!(lease.evidence, Some(evidence));
+
+    // The frozen payout is not released when the window closes
+    set_ledger_sequence(&env, end_block + 21);
+    assert!(client.try_complete_lease(&lease_id).is_err());
+
+    // The arbiter splits the frozen 25_000
+    client.resolve_dispute(&lease_id, &10_000);
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Completed));
+    assert_eq!(lease.price, 35_000);
+
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 35_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 9_965_000);
+}
+
+#[test]
+fn test_dispute_after_claiming_at_end_block() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    client.set_dispute_window(&20);
+    client.set_arbiter(&Address::generate(&env));
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let end_block = client.get_lease(&lease_id).end_block;
+
+    // The last 20 blocks are not streamed
+    set_ledger_sequence(&env, end_block);
+    assert_eq!(client.claim_lease_earnings(&lease_id), 40_000);
+
+    // and nothing can be claimed after the end block
+    set_ledger_sequence(&env, end_block + 1);
+    assert!(client.try_claim_lease_earnings(&lease_id).is_err());
+    client.complete_lease(&lease_id);
+    client.open_dispute(&lease_id, &BytesN::from_array(&env, &[7; 32]));
+
+    // The held back payout is frozen and the arbiter returns it to the tenant
+    client.resolve_dispute(&lease_id, &0);
+    assert_eq!(client.get_lease(&lease_id).price, 40_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 9_960_000);
+}
+
+#[test]
+#[should_panic(expected = "Dispute window of lease 1 is closed")]
+fn test_open_dispute_after_window() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+
+    client.set_dispute_window(&20);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let end_block = client.get_lease(&lease_id).end_block;
+
+    set_ledger_sequence(&env, end_block + 1);
+    client.complete_lease(&lease_id);
+
+    set_ledger_sequence(&env, end_block + 21);
+    client.open_dispute(&lease_id, &BytesN::from_array(&env, &[7; 32]));
+}
+
+#[test]
+fn test_migrate_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let replacement_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let start_block = client.get_lease(&lease_id).start_block;
+
+    set_ledger_sequence(&env, start_block + 40);
+    order_book.provider_registry.set_provider_status(
+        &Address::generate(&env),
+        &provider_id,
+        &ProviderStatus::Maintanance,
+    );
+    let order_id = client.migrate_lease(&lease_id);
+
+    // The failed provider is paid for 40 of 100 blocks
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Canceled));
+    assert_eq!(lease.price, 20_000);
+    assert_eq!(lease.end_block, start_block + 40);
+    assert_eq!(lease.successor_order, Some(order_id));
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 20_000);
+
+    // The replacement order covers the remaining blocks with the rest of the
+    // budget, which stays locked
+    let order = client.get_order(&order_id);
+    let original = client.get_order(&lease.order_id);
+    assert!(matches!(order.state, OrderState::Active));
+    assert_eq!(order.max_price, 30_000);
+    assert_eq!(order.number_of_blocks, 60);
+    assert_eq!(order.spec.spec, original.spec.spec);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 30_000);
+
+    let bid_id = client.place_bid(&order_id, &replacement_id, &25_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+    assert_eq!(client.get_lease(&lease_id).price, 25_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 25_000);
+}
+
+#[test]
+#[should_panic(expected = "Provider 1 is still serving lease 1")]
+fn test_migrate_lease_from_active_provider() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    client.migrate_lease(&lease_id);
+}
+
+#[test]
+fn test_lease_deployment() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let key = BytesN::from_array(&env, &[3; 32]);
+    order_book
+        .provider_registry
+        .set_encryption_key(&provider_id, &key);
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    let secrets_uri = String::from_str(&env, "ipfs://bafysecrets");
+    client.set_deployment(&lease_id, &manifest_hash(&env), &secrets_uri);
+
+    let lease = client.get_lease(&lease_id);
+    assert_eq!(lease.manifest_hash, Some(manifest_hash(&env)));
+    assert_eq!(lease.secrets_uri, Some(secrets_uri));
+    assert_eq!(lease.secrets_key, Some(key));
+
+    // A different manifest is rejected
+    assert!(client
+        .try_activate_lease(
+            &lease_id,
+            &String::from_str(&env, "container-1"),
+            &BytesN::from_array(&env, &[6; 32]),
+        )
+        .is_err());
+
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Active));
+    assert_eq!(lease.deployed_manifest, Some(manifest_hash(&env)));
+}
+
+#[test]
+#[should_panic(expected = "Provider 1 has no encryption key")]
+fn test_set_deployment_without_encryption_key() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+
+    let lease_id = create_matched_lease(&env, &order_book, &tenant, provider_id, 50_000, 100);
+    client.set_deployment(
+        &lease_id,
+        &manifest_hash(&env),
+        &String::from_str(&env, "ipfs://bafysecrets"),
+    );
+}
+
+// Helper function to create a spot resource order with the given quantity
+fn create_spot_order(
+    env: &Env,
+    order_book: &OrderBookTest,
+    tenant: &Address,
+    quantity: u64,
+) -> u64 {
+    let mut terms = order_terms(env, 0);
+    terms.class = OrderClass::Spot;
+    order_book.client.create_order(
+        tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &quantity,
+        &OrderSpec::Resources(resource_spec(env)),
+        &vec![env, TrustLevel::One],
+        &(env.ledger().sequence() + 100),
+        &AuctionMode::Open,
+        &terms,
+    )
+}
+
+#[test]
+fn test_preempt_spot_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let spot_tenant = order_book.create_tenant(&env);
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    declare_capacity(&order_book, provider_id);
+
+    let order_id = create_spot_order(&env, &order_book, &spot_tenant, 2);
+    let bid_id = client.place_bid(&order_id, &provider_id, &40_000, &2);
+    let spot_lease_id = client.accept_bid(&bid_id);
+    client.activate_lease(
+        &spot_lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let start_block = client.get_lease(&spot_lease_id).start_block;
+
+    // Capacity held by spot leases does not stop the provider from bidding on
+    // regular orders, but the bid can not be matched while it is held
+    let order_id = create_resource_order(&env, &order_book, &tenant, 2);
+    let bid_id = client.place_bid(&order_id, &provider_id, &50_000, &2);
+    assert!(client.try_accept_bid(&bid_id).is_err());
+
+    // Naming the spot lease does not preempt it until the bid wins
+    client.set_bid_preemptions(&bid_id, &vec![&env, spot_lease_id]);
+    assert!(matches!(
+        client.get_lease(&spot_lease_id).state,
+        LeaseState::Active
+    ));
+
+    set_ledger_sequence(&env, start_block + 40);
+    let lease_id = client.accept_bid(&bid_id);
+    assert!(has_event(
+        &env,
+        &client.address,
+        (
+            Symbol::new(&env, "lease_preempted"),
+            1_u64,
+            provider_id,
+            spot_tenant.clone()
+        )
+            .into_val(&env),
+        (spot_lease_id, 32_000_u128).into_val(&env),
+    ));
+
+    // The spot tenant pays for the 40 blocks served and gets the rest back
+    let lease = client.get_lease(&spot_lease_id);
+    assert!(matches!(lease.state, LeaseState::Canceled));
+    assert_eq!(lease.price, 32_000);
+    assert_eq!(lease.end_block, start_block + 40);
+    let balance = order_book.escrow.get_tenant_balance(&token, &spot_tenant);
+    assert_eq!(balance.locked_balance, 0);
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 32_000);
+    assert_eq!(client.get_free_capacity(&provider_id), Capacity::zero());
+    assert!(matches!(
+        client.get_lease(&lease_id).class,
+        OrderClass::Regular
+    ));
+}
+
+#[test]
+fn test_canceled_bid_does_not_preempt() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    declare_capacity(&order_book, provider_id);
+
+    let order_id = create_spot_order(&env, &order_book, &tenant, 2);
+    let bid_id = client.place_bid(&order_id, &provider_id, &40_000, &2);
+    let spot_lease_id = client.accept_bid(&bid_id);
+
+    let order_id = create_resource_order(&env, &order_book, &tenant, 2);
+    let bid_id = client.place_bid(&order_id, &provider_id, &90_000, &2);
+    client.set_bid_preemptions(&bid_id, &vec![&env, spot_lease_id]);
+    client.cancel_bid(&bid_id);
+
+    assert!(matches!(
+        client.get_lease(&spot_lease_id).state,
+        LeaseState::Initiate
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Bid with ID 2 does not pay more than lease 1")]
+fn test_preempt_spot_lease_with_lower_bid() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    declare_capacity(&order_book, provider_id);
+
+    let order_id = create_spot_order(&env, &order_book, &tenant, 2);
+    let bid_id = client.place_bid(&order_id, &provider_id, &40_000, &2);
+    let lease_id = client.accept_bid(&bid_id);
+
+    let order_id = create_resource_order(&env, &order_book, &tenant, 2);
+    let bid_id = client.place_bid(&order_id, &provider_id, &30_000, &2);
+    client.set_bid_preemptions(&bid_id, &vec![&env, lease_id]);
+}
+
+#[test]
+#[should_panic(expected = "Lease with ID 1 is not a spot lease")]
+fn test_preempt_regular_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    declare_capacity(&order_book, provider_id);
+
+    let order_id = create_resource_order(&env, &order_book, &tenant, 1);
+    let bid_id = client.place_bid(&order_id, &provider_id, &40_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+
+    let order_id = create_resource_order(&env, &order_book, &tenant, 2);
+    let bid_id = client.place_bid(&order_id, &provider_id, &90_000, &1);
+    client.set_bid_preemptions(&bid_id, &vec![&env, lease_id]);
+}
+
+// Helper function to store a text order template
+fn create_template(
+    env: &Env,
+    order_book: &OrderBookTest,
+    tenant: &Address,
+    max_price: u128,
+    recurrence: u32,
+) -> u64 {
+    order_book.client.create_template(
+        tenant,
+        &order_book.token.address,
+        &max_price,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(env, "cpu:4,memory:8G")),
+        &vec![env, TrustLevel::One],
+        &50,
+        &order_terms(env, 0),
+        &recurrence,
+    )
+}
+
+#[test]
+fn test_create_order_from_template() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+
+    // Storing a template locks nothing
+    let template_id = create_template(&env, &order_book, &tenant, 100_000, 0);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+
+    let order_id = client.create_order_from_template(&template_id);
+    let order = client.get_order(&order_id);
+    assert!(matches!(order.state, OrderState::Active));
+    assert_eq!(order.tenant, tenant);
+    assert_eq!(order.max_price, 100_000);
+    assert_eq!(order.number_of_blocks, 100);
+    assert_eq!(order.bid_deadline, env.ledger().sequence() + 50);
+    assert_eq!(client.get_template(&template_id).last_order, Some(order_id));
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 100_000);
+
+    // Templates without a recurrence can not be run by keepers
+    assert!(client.try_run_template(&template_id).is_err());
+
+    client.remove_template(&template_id);
+    assert!(client.try_get_template(&template_id).is_err());
+}
+
+#[test]
+fn test_run_recurring_template() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let template_id = create_template(&env, &order_book, &tenant, 4_000_000, 720);
+    let start = env.ledger().sequence();
+
+    let order_id = client.run_template(&template_id);
+    assert_eq!(client.get_order(&order_id).bid_deadline, start + 50);
+    assert_eq!(client.get_template(&template_id).next_run, start + 720);
+
+    // The next instance is not due yet
+    assert!(client.try_run_template(&template_id).is_err());
+
+    set_ledger_sequence(&env, start + 720);
+    let next_id = client.run_template(&template_id);
+    assert_ne!(next_id, order_id);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 8_000_000);
+
+    // The tenant's balance can not fund a third instance
+    set_ledger_sequence(&env, start + 1440);
+    assert!(client.try_run_template(&template_id).is_err());
+}
+
+#[test]
+#[should_panic(expected = "Number of blocks must be positive")]
+fn test_create_invalid_template() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+
+    order_book.client.create_template(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &0,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &50,
+        &order_terms(&env, 0),
+        &0,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Forward orders can not be templated")]
+fn test_create_forward_template() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+
+    let mut terms = order_terms(&env, 0);
+    terms.class = OrderClass::Forward(ForwardTerms {
+        start_block: 500,
+        deposit_bps: 2_000,
+        funding_blocks: 200,
+    });
+    order_book.client.create_template(
+        &tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(&env, "cpu:4,memory:8G")),
+        &vec![&env, TrustLevel::One],
+        &50,
+        &terms,
+        &0,
+    );
+}
+
+// Helper function to create a forward order reserving one unit from
+// `start_block` with a 20% deposit and a 200 ledger funding window
+fn create_forward_order(
+    env: &Env,
+    order_book: &OrderBookTest,
+    tenant: &Address,
+    start_block: u32,
+) -> u64 {
+    let mut terms = order_terms(env, 0);
+    terms.class = OrderClass::Forward(ForwardTerms {
+        start_block,
+        deposit_bps: 2_000,
+        funding_blocks: 200,
+    });
+    order_book.client.create_order(
+        tenant,
+        &order_book.token.address,
+        &100_000,
+        &100,
+        &1,
+        &OrderSpec::Text(String::from_str(env, "cpu:4,memory:8G")),
+        &vec![env, TrustLevel::One],
+        &(env.ledger().sequence() + 100),
+        &AuctionMode::Open,
+        &terms,
+    )
+}
+
+#[test]
+fn test_forward_order_lifecycle() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let start_block = env.ledger().sequence() + 500;
+
+    // Only the deposit is locked for the order and its lease
+    let order_id = create_forward_order(&env, &order_book, &tenant, start_block);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 20_000);
+
+    let bid_id = client.place_bid(&order_id, &provider_id, &50_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+    let lease = client.get_lease(&lease_id);
+    assert_eq!(lease.start_block, start_block);
+    assert_eq!(lease.end_block, start_block + 100);
+    assert_eq!(lease.unfunded, 40_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 10_000);
+
+    // The rest can only be locked once the funding window opens
+    assert!(client.try_fund_lease(&lease_id).is_err());
+    set_ledger_sequence(&env, start_block - 200);
+    client.fund_lease(&lease_id);
+    assert_eq!(client.get_lease(&lease_id).unfunded, 0);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 50_000);
+
+    // The provider can not activate the lease before the reserved window
+    assert!(client
+        .try_activate_lease(
+            &lease_id,
+            &String::from_str(&env, "container-1"),
+            &manifest_hash(&env),
+        )
+        .is_err());
+
+    set_ledger_sequence(&env, start_block);
+    client.activate_lease(
+        &lease_id,
+        &String::from_str(&env, "container-1"),
+        &manifest_hash(&env),
+    );
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Active));
+    assert_eq!(lease.start_block, start_block);
+}
+
+#[test]
+fn test_forward_lease_lapses_without_funding() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let start_block = env.ledger().sequence() + 500;
+
+    let order_id = create_forward_order(&env, &order_book, &tenant, start_block);
+    let bid_id = client.place_bid(&order_id, &provider_id, &50_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+
+    assert!(client.try_lapse_lease(&lease_id).is_err());
+
+    // The tenant did not top up by the start, so the provider keeps the
+    // deposit
+    set_ledger_sequence(&env, start_block);
+    assert!(client.try_fund_lease(&lease_id).is_err());
+    client.lapse_lease(&lease_id);
+
+    let lease = client.get_lease(&lease_id);
+    assert!(matches!(lease.state, LeaseState::Canceled));
+    assert_eq!(lease.price, 10_000);
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 10_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+}
+
+#[test]
+#[should_panic(expected = "Lease with ID 1 is not awaiting activation")]
+fn test_forfeit_bond_of_unfunded_forward_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    order_book.fund_provider(provider_id);
+    client.set_bid_bond(&10, &20);
+    let start_block = env.ledger().sequence() + 500;
+
+    let order_id = create_forward_order(&env, &order_book, &tenant, start_block);
+    let bid_id = client.place_bid(&order_id, &provider_id, &50_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+
+    // The tenant never topped up, so the lease can only lapse
+    set_ledger_sequence(&env, start_block + 21);
+    client.forfeit_bid_bond(&lease_id);
+}
+
+#[test]
+fn test_tenant_cancels_funded_forward_lease() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let start_block = env.ledger().sequence() + 500;
+
+    let order_id = create_forward_order(&env, &order_book, &tenant, start_block);
+    let bid_id = client.place_bid(&order_id, &provider_id, &50_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+    assert_eq!(client.get_lease(&lease_id).deposit, 10_000);
+
+    set_ledger_sequence(&env, start_block - 200);
+    client.fund_lease(&lease_id);
+
+    // Backing out before the start only forfeits the deposit
+    client.cancel_lease(&tenant, &lease_id);
+    let earnings = order_book
+        .escrow
+        .get_provider_earnings(&provider_id, &token);
+    assert_eq!(earnings.earned, 10_000);
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 10_000_000 - 10_000);
+}
+
+#[test]
+fn test_tenant_cancels_forward_lease_never_activated() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let client = &order_book.client;
+    let tenant = order_book.create_tenant(&env);
+    let token = order_book.token.address.clone();
+    let provider_id = register_provider(
+        &env,
+        &order_book.provider_registry,
+        provider_registry::TrustLevel::One,
+    );
+    let start_block = env.ledger().sequence() + 500;
+
+    let order_id = create_forward_order(&env, &order_book, &tenant, start_block);
+    let bid_id = client.place_bid(&order_id, &provider_id, &50_000, &1);
+    let lease_id = client.accept_bid(&bid_id);
+
+    set_ledger_sequence(&env, start_block - 200);
+    client.fund_lease(&lease_id);
+
+    // The provider did not show up, so the tenant keeps its deposit
+    set_ledger_sequence(&env, start_block + 10);
+    client.cancel_lease(&tenant, &lease_id);
+    assert!(order_book
+        .escrow
+        .try_get_provider_earnings(&provider_id, &token)
+        .is_err());
+    let balance = order_book.escrow.get_tenant_balance(&token, &tenant);
+    assert_eq!(balance.locked_balance, 0);
+    assert_eq!(balance.unlocked_balance, 10_000_000);
+}
+
+#[test]
+#[should_panic(expected = "Forward start must be after the bid deadline")]
+fn test_forward_order_starting_before_bid_deadline() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+
+    create_forward_order(&env, &order_book, &tenant, env.ledger().sequence() + 50);
+}
+
+#[test]
+#[should_panic(expected = "Forward start must be after the bid deadline")]
+fn test_amend_forward_order_past_start() {
+    let env = Env::default();
+    let order_book = create_order_book(&env);
+    let tenant = order_book.create_tenant(&env);
+
+    let start_block = env.ledger().sequence() + 300;
+    let order_id = create_forward_order(&env, &order_book, &tenant, start_block);
+    order_book.client.amend_order(
+        &order_id,
+        &100_000,
+        &start_block,
+        &vec![&env, TrustLevel::One],
+    );
+}
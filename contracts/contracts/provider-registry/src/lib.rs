@@ -1,6 +1,6 @@
 #![no_std]
 
-use soroban_sdk::{contract, contractimpl, contracttype, Address, Env, Vec};
+use soroban_sdk::{contract, contractimpl, contracttype, Address, BytesN, Env, Vec};
 
 #[contract]
 pub struct ProviderRegistryContract;
@@ -36,15 +36,38 @@ pub enum ProviderStatus {
 #[contracttype]
 #[derive(Debug, Clone)]
 pub struct Provider {
-    address: Address,
-    trust_level: TrustLevel,
-    status: ProviderStatus,
+    pub address: Address,
+    pub trust_level: TrustLevel,
+    pub status: ProviderStatus,
+}
+
+// Resources a provider can run, or has committed, across all its leases.
+#[contracttype]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capacity {
+    pub cpu_millis: u64,
+    pub memory_mb: u64,
+    pub storage_gb: u64,
+    pub gpus: u64,
+}
+
+impl Capacity {
+    pub fn zero() -> Self {
+        Capacity {
+            cpu_millis: 0,
+            memory_mb: 0,
+            storage_gb: 0,
+            gpus: 0,
+        }
+    }
 }
 
 #[contracttype]
 pub enum Providers {
     ProviderIdToProvider(u64),
     AddressToProviderId(Address),
+    Capacity(u64),
+    EncryptionKey(u64),
 }
 
 impl Provider {
@@ -93,6 +116,40 @@ impl ProviderRegistryContract {
             .set(&Providers::ProviderIdToProvider(provider_id), &provider);
     }
 
+    // Declares the resources the provider can run across all its leases.
+    pub fn set_capacity(env: Env, provider_id: u64, capacity: Capacity) {
+        let provider = ProviderRegistryContract::get_provider(env.clone(), provider_id);
+        provider.address.require_auth();
+
+        env.storage()
+            .instance()
+            .set(&Providers::Capacity(provider_id), &capacity);
+    }
+
+    pub fn get_capacity(env: Env, provider_id: u64) -> Capacity {
+        env.storage()
+            .instance()
+            .get(&Providers::Capacity(provider_id))
+            .unwrap_or(Capacity::zero())
+    }
+
+    // Registers the public key tenants encrypt deployment secrets to.
+    pub fn set_encryption_key(env: Env, provider_id: u64, key: BytesN<32>) {
+        let provider = ProviderRegistryContract::get_provider(env.clone(), provider_id);
+        provider.address.require_auth();
+
+        env.storage()
+            .instance()
+            .set(&Providers::EncryptionKey(provider_id), &key);
+    }
+
+    pub fn get_encryption_key(env: Env, provider_id: u64) -> BytesN<32> {
+        env.storage()
+            .instance()
+            .get(&Providers::EncryptionKey(provider_id))
+            .unwrap_or_else(|| panic!("Provider {} has no encryption key", provider_id))
+    }
+
     pub fn get_provider(env: Env, provider_id: u64) -> Provider {
         env.storage()
             .instance()
@@ -163,7 +220,7 @@ impl ProviderRegistryContract {
             .unwrap_or(0);
 
         provider_count += 1;
-        let provider_id = provider_count.clone();
+        let provider_id = provider_count;
         let provider = Provider::new(address.clone());
 
         env.storage()
@@ -6,7 +6,7 @@ use soroban_sdk::{testutils::Address as _, Address, Env};
 #[test]
 fn test_add_provider() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Create a test provider address
@@ -26,7 +26,7 @@ fn test_add_provider() {
 #[test]
 fn test_add_multiple_providers() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Create test provider addresses
@@ -52,7 +52,7 @@ fn test_add_multiple_providers() {
 #[test]
 fn test_add_existing_provider() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Create a test provider address
@@ -73,7 +73,7 @@ fn test_add_existing_provider() {
 #[test]
 fn test_set_trust_level() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Create a test provider and verifier address
@@ -94,10 +94,47 @@ fn test_set_trust_level() {
     assert!(matches!(provider.trust_level, TrustLevel::Two));
 }
 
+#[test]
+fn test_set_capacity() {
+    let env = Env::default();
+    let contract_id = env.register(ProviderRegistryContract, ());
+    let client = ProviderRegistryContractClient::new(&env, &contract_id);
+
+    let provider_id = client.add_provider(&Address::generate(&env));
+    assert_eq!(client.get_capacity(&provider_id), Capacity::zero());
+
+    env.mock_all_auths();
+
+    let capacity = Capacity {
+        cpu_millis: 64_000,
+        memory_mb: 262_144,
+        storage_gb: 4_000,
+        gpus: 8,
+    };
+    client.set_capacity(&provider_id, &capacity);
+    assert_eq!(client.get_capacity(&provider_id), capacity);
+}
+
+#[test]
+fn test_set_encryption_key() {
+    let env = Env::default();
+    let contract_id = env.register(ProviderRegistryContract, ());
+    let client = ProviderRegistryContractClient::new(&env, &contract_id);
+
+    let provider_id = client.add_provider(&Address::generate(&env));
+    assert!(client.try_get_encryption_key(&provider_id).is_err());
+
+    env.mock_all_auths();
+
+    let key = BytesN::from_array(&env, &[3; 32]);
+    client.set_encryption_key(&provider_id, &key);
+    assert_eq!(client.get_encryption_key(&provider_id), key);
+}
+
 #[test]
 fn test_set_provider_status() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Create a test provider and admin address
@@ -136,7 +173,7 @@ fn test_set_provider_status() {
 #[test]
 fn test_get_provider_by_address() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Create test provider addresses
@@ -161,7 +198,7 @@ fn test_get_provider_by_address() {
 #[test]
 fn test_list_providers() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Initially, there should be no providers
@@ -191,7 +228,7 @@ fn test_list_providers() {
 #[should_panic(expected = "Provider with ID 999 not found")]
 fn test_get_nonexistent_provider() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Try to get a provider that doesn't exist
@@ -202,7 +239,7 @@ fn test_get_nonexistent_provider() {
 #[should_panic(expected = "Provider with address not found")]
 fn test_get_nonexistent_provider_by_address() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Create an address that hasn't been registered
@@ -215,7 +252,7 @@ fn test_get_nonexistent_provider_by_address() {
 #[test]
 fn test_deactivate_and_reactivate_provider() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Create a test provider and admin address
@@ -246,7 +283,7 @@ fn test_deactivate_and_reactivate_provider() {
 #[test]
 fn test_full_provider_lifecycle() {
     let env = Env::default();
-    let contract_id = env.register(ProviderRegistryContract, {});
+    let contract_id = env.register(ProviderRegistryContract, ());
     let client = ProviderRegistryContractClient::new(&env, &contract_id);
 
     // Generate addresses
@@ -1,8 +1,6 @@
 import { Request, Response } from 'express';
-import { Keypair } from '@stellar/stellar-sdk';
 import { leaseService } from '../../services/lease';
 import { createLogger } from '../../utils/logger';
-import config from '../../config';
 
 const logger = createLogger('lease-controller');
 
@@ -11,52 +9,20 @@ const logger = createLogger('lease-controller');
  */
 class LeaseController {
   /**
-   * Create a new lease
+   * Deploy a lease created by accepting a bid
    * @param req Request
    * @param res Response
    */
   public createLease = async (req: Request, res: Response): Promise<void> => {
     try {
-      const {
-        orderId,
-        providerId,
-        startBlock,
-        endBlock,
-        tenantAddress,
-      } = req.body;
+      const { leaseId } = req.body;
       
-      if (!orderId) {
-        res.status(400).json({ error: { message: 'Order ID is required' } });
-        return;
-      }
-      
-      if (!providerId) {
-        res.status(400).json({ error: { message: 'Provider ID is required' } });
-        return;
-      }
-      
-      if (!startBlock) {
-        res.status(400).json({ error: { message: 'Start block is required' } });
-        return;
-      }
-      
-      if (!endBlock) {
-        res.status(400).json({ error: { message: 'End block is required' } });
-        return;
-      }
-      
-      if (!tenantAddress) {
-        res.status(400).json({ error: { message: 'Tenant address is required' } });
+      if (!leaseId) {
+        res.status(400).json({ error: { message: 'Lease ID is required' } });
         return;
       }
       
-      const lease = await leaseService.createLease(
-        orderId,
-        providerId,
-        startBlock,
-        endBlock,
-        tenantAddress
-      );
+      const lease = await leaseService.createLease(leaseId);
       
       res.status(201).json({ lease });
     } catch (error) {
@@ -84,12 +50,7 @@ class LeaseController {
         return;
       }
       
-      const lease = leaseService.getLease(parseInt(id));
-      
-      if (!lease) {
-        res.status(404).json({ error: { message: 'Lease not found' } });
-        return;
-      }
+      const lease = await leaseService.getLease(parseInt(id));
       
       res.status(200).json({ lease });
     } catch (error) {
@@ -110,7 +71,10 @@ class LeaseController {
    */
   public listLeases = async (req: Request, res: Response): Promise<void> => {
     try {
-      const leases = leaseService.listLeases();
+      const cursor = parseInt(req.query.cursor as string || '0');
+      const limit = parseInt(req.query.limit as string || '50');
+      
+      const leases = await leaseService.listLeases(cursor, limit);
       
       res.status(200).json({ leases });
     } catch (error) {
@@ -188,40 +152,15 @@ class LeaseController {
   public processPayment = async (req: Request, res: Response): Promise<void> => {
     try {
       const { id } = req.params;
-      const { tokenAddress, tenantAddress, amount } = req.body;
       
       if (!id) {
         res.status(400).json({ error: { message: 'Lease ID is required' } });
         return;
       }
       
-      if (!tokenAddress) {
-        res.status(400).json({ error: { message: 'Token address is required' } });
-        return;
-      }
+      const payment = await leaseService.processPayment(parseInt(id));
       
-      if (!tenantAddress) {
-        res.status(400).json({ error: { message: 'Tenant address is required' } });
-        return;
-      }
-      
-      if (!amount) {
-        res.status(400).json({ error: { message: 'Amount is required' } });
-        return;
-      }
-      
-      // Use admin keypair for payment processing
-      const adminKeypair = Keypair.fromSecret(config.soroban.signerSecretKey);
-      
-      const success = await leaseService.processPayment(
-        parseInt(id),
-        tokenAddress,
-        tenantAddress,
-        amount,
-        adminKeypair
-      );
-      
-      res.status(200).json({ success });
+      res.status(200).json(payment);
     } catch (error) {
       logger.error(`Error processing payment for lease ${req.params.id}:`, error);
       res.status(500).json({
@@ -303,7 +242,10 @@ class LeaseController {
         return;
       }
       
-      const leases = leaseService.listLeasesForProvider(parseInt(id));
+      const cursor = parseInt(req.query.cursor as string || '0');
+      const limit = parseInt(req.query.limit as string || '50');
+      
+      const leases = await leaseService.listLeasesForProvider(parseInt(id), cursor, limit);
       
       res.status(200).json({ leases });
     } catch (error) {
@@ -331,7 +273,7 @@ class LeaseController {
         return;
       }
       
-      const leases = leaseService.listLeasesForOrder(parseInt(id));
+      const leases = await leaseService.listLeasesForOrder(parseInt(id));
       
       res.status(200).json({ leases });
     } catch (error) {
@@ -1,6 +1,6 @@
 import { Request, Response } from 'express';
 import { orderService } from '../../services/order';
-import { TrustLevel } from '../../types/contracts';
+import { OrderState, TrustLevel } from '../../types/contracts';
 import { ContainerSpecification } from '../../types/models';
 import { createLogger } from '../../utils/logger';
 
@@ -18,13 +18,20 @@ class OrderController {
   public createOrder = async (req: Request, res: Response): Promise<void> => {
     try {
       const {
+        tokenAddress,
         maxPrice,
         specification,
         trustLevels,
         quantity,
         durationBlocks,
+        bidDeadline,
       } = req.body;
       
+      if (!tokenAddress) {
+        res.status(400).json({ error: { message: 'Token address is required' } });
+        return;
+      }
+      
       if (!maxPrice) {
         res.status(400).json({ error: { message: 'Max price is required' } });
         return;
@@ -55,12 +62,19 @@ class OrderController {
         return;
       }
       
+      if (!bidDeadline) {
+        res.status(400).json({ error: { message: 'Bid deadline is required' } });
+        return;
+      }
+      
       const order = await orderService.createOrder(
+        tokenAddress,
         maxPrice,
         specification as ContainerSpecification,
         trustLevels as TrustLevel[],
         quantity,
-        durationBlocks
+        durationBlocks,
+        bidDeadline
       );
       
       res.status(201).json({ order });
@@ -110,7 +124,11 @@ class OrderController {
    */
   public listOrders = async (req: Request, res: Response): Promise<void> => {
     try {
-      const orders = await orderService.listOrders();
+      const cursor = parseInt(req.query.cursor as string || '0');
+      const limit = parseInt(req.query.limit as string || '50');
+      const states = req.query.state ? [req.query.state as OrderState] : [];
+      
+      const orders = await orderService.listOrders({ states }, cursor, limit);
       
       res.status(200).json({ orders });
     } catch (error) {
@@ -160,7 +178,7 @@ class OrderController {
   public placeBid = async (req: Request, res: Response): Promise<void> => {
     try {
       const { id } = req.params;
-      const { providerId, bidPrice } = req.body;
+      const { providerId, bidPrice, quantity = 1 } = req.body;
       
       if (!id) {
         res.status(400).json({ error: { message: 'Order ID is required' } });
@@ -180,7 +198,8 @@ class OrderController {
       const bid = await orderService.placeBid(
         parseInt(id),
         providerId,
-        bidPrice
+        bidPrice,
+        quantity
       );
       
       res.status(201).json({ bid });
@@ -237,7 +256,10 @@ class OrderController {
         return;
       }
       
-      const bids = await orderService.getBidsForOrder(parseInt(id));
+      const cursor = parseInt(req.query.cursor as string || '0');
+      const limit = parseInt(req.query.limit as string || '50');
+      
+      const bids = await orderService.getBidsForOrder(parseInt(id), cursor, limit);
       
       res.status(200).json({ bids });
     } catch (error) {
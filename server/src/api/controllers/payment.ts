@@ -59,56 +59,6 @@ class PaymentController {
     }
   };
 
-  /**
-   * Lock funds
-   * @param req Request
-   * @param res Response
-   */
-  public lockFunds = async (req: Request, res: Response): Promise<void> => {
-    try {
-      const { tokenAddress, tenantAddress, amount, secretKey } = req.body;
-      
-      if (!tokenAddress) {
-        res.status(400).json({ error: { message: 'Token address is required' } });
-        return;
-      }
-      
-      if (!tenantAddress) {
-        res.status(400).json({ error: { message: 'Tenant address is required' } });
-        return;
-      }
-      
-      if (!amount) {
-        res.status(400).json({ error: { message: 'Amount is required' } });
-        return;
-      }
-      
-      if (!secretKey) {
-        res.status(400).json({ error: { message: 'Secret key is required for authentication' } });
-        return;
-      }
-      
-      const tenantKeypair = Keypair.fromSecret(secretKey);
-      
-      const payment = await paymentService.lockFunds(
-        tokenAddress,
-        tenantAddress,
-        amount,
-        tenantKeypair
-      );
-      
-      res.status(201).json({ payment });
-    } catch (error) {
-      logger.error('Error locking funds:', error);
-      res.status(500).json({
-        error: {
-          message: 'Failed to lock funds',
-          details: error instanceof Error ? error.message : String(error),
-        },
-      });
-    }
-  };
-
   /**
    * Withdraw provider earnings
    * @param req Request
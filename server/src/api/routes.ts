@@ -61,7 +61,6 @@ export const setupRoutes = (app: express.Application): void => {
   app.get(`${apiPrefix}/payments`, paymentController.listPayments);
   app.get(`${apiPrefix}/payments/:id`, paymentController.getPayment);
   app.post(`${apiPrefix}/payments/deposit`, paymentController.depositFunds);
-  app.post(`${apiPrefix}/payments/lock`, paymentController.lockFunds);
   app.post(`${apiPrefix}/payments/withdraw`, paymentController.withdrawProviderEarnings);
   app.get(`${apiPrefix}/tenants/:address/balance`, paymentController.getTenantBalance);
   app.get(`${apiPrefix}/providers/:id/earnings`, paymentController.getProviderEarnings);
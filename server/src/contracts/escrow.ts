@@ -92,104 +92,6 @@ export class EscrowContract {
     }
   }
 
-  /**
-   * Lock funds in the escrow
-   * @param tokenAddress Token address
-   * @param from Sender address
-   * @param amount Amount to lock
-   * @param senderKeypair Sender keypair for authentication
-   */
-  public async lock(
-    tokenAddress: string,
-    from: string,
-    amount: string,
-    senderKeypair: Keypair
-  ): Promise<void> {
-    try {
-      logger.info(`Locking ${amount} from ${from}`);
-      const contract = sorobanClient.getContract(this.contractId);
-      
-      await sorobanClient.callMethod(
-        contract,
-        'lock',
-        [tokenAddress,
-        from,
-        amount]
-      );
-      
-      logger.info(`Locked ${amount} from ${from}`);
-    } catch (error) {
-      logger.error(`Failed to lock ${amount} from ${from}:`, error);
-      throw new Error(`Lock failed: ${error instanceof Error ? error.message : String(error)}`);
-    }
-  }
-
-  /**
-   * Unlock funds in the escrow
-   * @param tokenAddress Token address
-   * @param from Sender address
-   * @param amount Amount to unlock
-   */
-  public async unlockTokens(
-    tokenAddress: string,
-    from: string,
-    amount: string,
-    senderKeypair: Keypair
-  ): Promise<void> {
-    try {
-      logger.info(`Unlocking ${amount} from ${from}`);
-      const contract = sorobanClient.getContract(this.contractId);
-      
-      await sorobanClient.callMethod(
-        contract,
-        'unlock_tokens',
-        [tokenAddress,
-        from,
-        amount]
-      );
-      
-      logger.info(`Unlocked ${amount} from ${from}`);
-    } catch (error) {
-      logger.error(`Failed to unlock ${amount} from ${from}:`, error);
-      throw new Error(`Unlock failed: ${error instanceof Error ? error.message : String(error)}`);
-    }
-  }
-
-  /**
-   * Transfer locked funds to a provider
-   * @param tokenAddress Token address
-   * @param from Sender address
-   * @param amount Amount to transfer
-   * @param providerId Provider ID
-   * @param senderKeypair Sender keypair for authentication
-   */
-  public async transferLocked(
-    tokenAddress: string,
-    from: string,
-    amount: string,
-    providerId: number,
-    senderKeypair: Keypair
-  ): Promise<void> {
-    try {
-      logger.info(`Transferring ${amount} from ${from} to provider ${providerId}`);
-      const contract = sorobanClient.getContract(this.contractId);
-      
-      await sorobanClient.callMethod(
-        contract,
-        'transfer_locked',
-        [tokenAddress,
-        from,
-        amount,
-        providerId]
-      );
-      
-      logger.info(`Transferred ${amount} from ${from} to provider ${providerId}`);
-    } catch (error) {
-      logger.error(`Failed to transfer ${amount} from ${from} to provider ${providerId}:`, error);
-      throw new Error(`Transfer locked failed: ${error instanceof Error ? error.message : String(error)}`);
-    }
-  }
-
   /**
    * Withdraw provider earnings
    * @param tokenAddress Token address
@@ -1,11 +1,136 @@
-import { sorobanClient } from './client';
+import { addressToScVal, sorobanClient } from './client';
 import config from '../config';
-import { OrderRequest, TrustLevel } from '../types/contracts';
+import {
+  AuctionMode,
+  Lease,
+  LeaseOwner,
+  OrderFilter,
+  OrderRequest,
+  OrderSpec,
+  OrderState,
+  OrderTerms,
+  ProviderBid,
+  ResourceSpec,
+  TrustLevel,
+} from '../types/contracts';
 import { createLogger } from '../utils/logger';
-import { nativeToScVal } from '@stellar/stellar-sdk';
+import { nativeToScVal, xdr } from '@stellar/stellar-sdk';
 
 const logger = createLogger('order-book-contract');
 
+// Contract enums are encoded as a vector of the variant name and its value
+const enumToScVal = (variant: string, value?: xdr.ScVal) => {
+  const parts = [xdr.ScVal.scvSymbol(variant)];
+  if (value) {
+    parts.push(value);
+  }
+  return xdr.ScVal.scvVec(parts);
+};
+
+// Contract structs are encoded as maps keyed by field name in sorted order
+const structToScVal = (fields: Record<string, xdr.ScVal>) => {
+  return xdr.ScVal.scvMap(
+    Object.keys(fields)
+      .sort()
+      .map(key => new xdr.ScMapEntry({ key: xdr.ScVal.scvSymbol(key), val: fields[key] }))
+  );
+};
+
+const optionToScVal = <T>(value: T | undefined, encode: (value: T) => xdr.ScVal) => {
+  return value === undefined ? xdr.ScVal.scvVoid() : encode(value);
+};
+
+const u128ToScVal = (value: string) => nativeToScVal(BigInt(value), { type: "u128" });
+
+const trustLevelsToScVal = (trustLevels: TrustLevel[]) => {
+  return xdr.ScVal.scvVec(trustLevels.map(level => enumToScVal(level)));
+};
+
+const resourceSpecToScVal = (resources: ResourceSpec) => {
+  return structToScVal({
+    cpu_millis: nativeToScVal(resources.cpu_millis, { type: "u32" }),
+    memory_mb: nativeToScVal(resources.memory_mb, { type: "u64" }),
+    storage_gb: nativeToScVal(resources.storage_gb, { type: "u64" }),
+    gpus: nativeToScVal(resources.gpus, { type: "u32" }),
+    region: nativeToScVal(resources.region, { type: "string" }),
+    image_digest: xdr.ScVal.scvBytes(resources.image_digest),
+    ports: xdr.ScVal.scvVec(resources.ports.map(port => nativeToScVal(port, { type: "u32" }))),
+    capabilities: xdr.ScVal.scvVec(resources.capabilities.map(tag => xdr.ScVal.scvSymbol(tag))),
+  });
+};
+
+const orderSpecToScVal = (spec: OrderSpec) => {
+  switch (spec[0]) {
+    case 'Text':
+      return enumToScVal('Text', nativeToScVal(spec[1], { type: "string" }));
+    case 'Resources':
+      return enumToScVal('Resources', resourceSpecToScVal(spec[1]));
+  }
+};
+
+const auctionModeToScVal = (auction: AuctionMode) => {
+  switch (auction[0]) {
+    case 'Open':
+      return enumToScVal('Open');
+    case 'Sealed':
+      return enumToScVal('Sealed', nativeToScVal(auction[1], { type: "u32" }));
+    case 'Dutch':
+      return enumToScVal('Dutch', structToScVal({
+        floor_price: u128ToScVal(auction[1].floor_price),
+        decay_blocks: nativeToScVal(auction[1].decay_blocks, { type: "u32" }),
+      }));
+  }
+};
+
+const orderTermsToScVal = (terms: OrderTerms) => {
+  const orderClass = terms.class;
+  let classVal: xdr.ScVal;
+  switch (orderClass[0]) {
+    case 'Forward':
+      classVal = enumToScVal('Forward', structToScVal({
+        start_block: nativeToScVal(orderClass[1].start_block, { type: "u32" }),
+        deposit_bps: nativeToScVal(orderClass[1].deposit_bps, { type: "u32" }),
+        funding_blocks: nativeToScVal(orderClass[1].funding_blocks, { type: "u32" }),
+      }));
+      break;
+    default:
+      classVal = enumToScVal(orderClass[0]);
+  }
+
+  return structToScVal({
+    min_billing_blocks: nativeToScVal(terms.min_billing_blocks, { type: "u32" }),
+    sla: structToScVal({
+      min_uptime_bps: nativeToScVal(terms.sla.min_uptime_bps, { type: "u32" }),
+      max_latency_ms: nativeToScVal(terms.sla.max_latency_ms, { type: "u32" }),
+      downtime_budget: nativeToScVal(terms.sla.downtime_budget, { type: "u32" }),
+      penalties: xdr.ScVal.scvVec(terms.sla.penalties.map(penalty => structToScVal({
+        downtime_blocks: nativeToScVal(penalty.downtime_blocks, { type: "u32" }),
+        refund_bps: nativeToScVal(penalty.refund_bps, { type: "u32" }),
+      }))),
+    }),
+    class: classVal,
+  });
+};
+
+const leaseOwnerToScVal = (owner: LeaseOwner) => {
+  switch (owner[0]) {
+    case 'Tenant':
+      return enumToScVal('Tenant', addressToScVal(owner[1]));
+    case 'Provider':
+      return enumToScVal('Provider', nativeToScVal(owner[1], { type: "u64" }));
+  }
+};
+
+const orderFilterToScVal = (filter: OrderFilter) => {
+  return structToScVal({
+    states: xdr.ScVal.scvVec(filter.states.map((state: OrderState) => enumToScVal(state))),
+    tenant: optionToScVal(filter.tenant, addressToScVal),
+    token: optionToScVal(filter.token, addressToScVal),
+    min_price: optionToScVal(filter.min_price, u128ToScVal),
+    max_price: optionToScVal(filter.max_price, u128ToScVal),
+  });
+};
+
 /**
  * OrderBook Contract client for interacting with the order book contract
  */
@@ -28,19 +153,29 @@ export class OrderBookContract {
 
   /**
    * Create a new order
-   * @param maxPrice Maximum price per block
+   * @param tenant Tenant address paying for the order
+   * @param token Token the order is priced in
+   * @param maxPrice Maximum price per instance
    * @param numberOfBlocks Number of blocks the order should run for
    * @param quantity Number of instances
-   * @param spec Container specification JSON string
+   * @param spec Free-form or typed resource specification
    * @param trustLevels Required trust levels
+   * @param bidDeadline Last block bids are accepted in
+   * @param auction Auction mode
+   * @param terms Terms carried over into the order's leases
    * @returns Order ID
    */
   public async createOrder(
+    tenant: string,
+    token: string,
     maxPrice: string, 
     numberOfBlocks: number, 
     quantity: number, 
-    spec: string, 
-    trustLevels: TrustLevel[]
+    spec: OrderSpec, 
+    trustLevels: TrustLevel[],
+    bidDeadline: number,
+    auction: AuctionMode,
+    terms: OrderTerms
   ): Promise<number> {
     try {
       logger.info(`Creating order with max price ${maxPrice}, ${numberOfBlocks} blocks`);
@@ -49,11 +184,16 @@ export class OrderBookContract {
       const result = await sorobanClient.callMethod<number>(
         contract,
         'create_order',
-        [nativeToScVal(maxPrice, {type: "u128"}),
+        [addressToScVal(tenant),
+        addressToScVal(token),
+        u128ToScVal(maxPrice),
         nativeToScVal(numberOfBlocks, {type: "u32"}),
         nativeToScVal(quantity, {type: "u64"}),
-        nativeToScVal(spec, { type: "symbol"}),
-        nativeToScVal(trustLevels)],
+        orderSpecToScVal(spec),
+        trustLevelsToScVal(trustLevels),
+        nativeToScVal(bidDeadline, {type: "u32"}),
+        auctionModeToScVal(auction),
+        orderTermsToScVal(terms)],
       );
       
       logger.info(`Order created with ID ${result}`);
@@ -111,18 +251,27 @@ export class OrderBookContract {
   }
 
   /**
-   * List all orders
-   * @returns List of orders
+   * List orders matching a filter
+   * @param filter Order filter
+   * @param cursor Order ID to continue after, 0 for the first page
+   * @param limit Maximum number of orders to return
+   * @returns List of order IDs and orders
    */
-  public async listOrders(): Promise<OrderRequest[]> {
+  public async listOrders(
+    filter: OrderFilter,
+    cursor: number,
+    limit: number
+  ): Promise<Array<[number, OrderRequest]>> {
     try {
-      logger.info('Listing all orders');
+      logger.info(`Listing orders after ${cursor}`);
       const contract = sorobanClient.getContract(this.contractId);
       
-      const result = await sorobanClient.callMethod<OrderRequest[]>(
+      const result = await sorobanClient.callMethod<Array<[number, OrderRequest]>>(
         contract,
         'list_orders',
-        null
+        [orderFilterToScVal(filter),
+        nativeToScVal(cursor, {type: "u64"}),
+        nativeToScVal(limit, {type: "u32"})]
       );
       
       logger.info(`Retrieved ${result.length} orders`);
@@ -137,20 +286,27 @@ export class OrderBookContract {
    * Place a bid on an order
    * @param orderId Order ID
    * @param providerId Provider ID
-   * @param bidPrice Bid price
+   * @param bidPrice Bid price per instance
+   * @param quantity Number of instances bid for
    * @returns Bid ID
    */
-  public async placeBid(orderId: number, providerId: number, bidPrice: string): Promise<number> {
+  public async placeBid(
+    orderId: number,
+    providerId: number,
+    bidPrice: string,
+    quantity: number
+  ): Promise<number> {
     try {
-      logger.info(`Placing bid on order ${orderId} by provider ${providerId} for ${bidPrice}`);
+      logger.info(`Placing bid on order ${orderId} by provider ${providerId} for ${quantity} x ${bidPrice}`);
       const contract = sorobanClient.getContract(this.contractId);
       
       const result = await sorobanClient.callMethod<number>(
         contract,
         'place_bid',
-        [orderId,
-        providerId,
-        bidPrice]
+        [nativeToScVal(orderId, {type: "u64"}),
+        nativeToScVal(providerId, {type: "u64"}),
+        u128ToScVal(bidPrice),
+        nativeToScVal(quantity, {type: "u64"})]
       );
       
       logger.info(`Bid placed with ID ${result}`);
@@ -164,24 +320,208 @@ export class OrderBookContract {
   /**
    * Accept a bid
    * @param bidId Bid ID
+   * @returns Lease ID
    */
-  public async acceptBid(bidId: number): Promise<void> {
+  public async acceptBid(bidId: number): Promise<number> {
     try {
       logger.info(`Accepting bid with ID ${bidId}`);
       const contract = sorobanClient.getContract(this.contractId);
       
-      await sorobanClient.callMethod<void>(
+      const result = await sorobanClient.callMethod<number>(
         contract,
         'accept_bid',
         bidId
       );
       
-      logger.info(`Bid ${bidId} accepted and lease created`);
+      logger.info(`Bid ${bidId} accepted and lease ${result} created`);
+      return result;
     } catch (error) {
       logger.error(`Failed to accept bid ${bidId}:`, error);
       throw new Error(`Accept bid failed: ${error instanceof Error ? error.message : String(error)}`);
     }
   }
+
+  /**
+   * List the bids placed on an order
+   * @param orderId Order ID
+   * @param cursor Offset into the order's bids
+   * @param limit Maximum number of bids to return
+   * @returns List of bid IDs and bids
+   */
+  public async listBidsForOrder(
+    orderId: number,
+    cursor: number,
+    limit: number
+  ): Promise<Array<[number, ProviderBid]>> {
+    try {
+      logger.info(`Listing bids for order ${orderId}`);
+      const contract = sorobanClient.getContract(this.contractId);
+      
+      const result = await sorobanClient.callMethod<Array<[number, ProviderBid]>>(
+        contract,
+        'list_bids_for_order',
+        [nativeToScVal(orderId, {type: "u64"}),
+        nativeToScVal(cursor, {type: "u32"}),
+        nativeToScVal(limit, {type: "u32"})]
+      );
+      
+      logger.info(`Retrieved ${result.length} bids for order ${orderId}`);
+      return result;
+    } catch (error) {
+      logger.error(`Failed to list bids for order ${orderId}:`, error);
+      throw new Error(`List bids failed: ${error instanceof Error ? error.message : String(error)}`);
+    }
+  }
+
+  /**
+   * Get a lease by ID
+   * @param leaseId Lease ID
+   * @returns Lease
+   */
+  public async getLease(leaseId: number): Promise<Lease> {
+    try {
+      logger.info(`Getting lease with ID ${leaseId}`);
+      const contract = sorobanClient.getContract(this.contractId);
+      
+      const result = await sorobanClient.callMethod<Lease>(
+        contract,
+        'get_lease',
+        nativeToScVal(leaseId, { type: "u64"})
+      );
+      
+      logger.info(`Retrieved lease ${leaseId}`);
+      return result;
+    } catch (error) {
+      logger.error(`Failed to get lease ${leaseId}:`, error);
+      throw new Error(`Get lease failed: ${error instanceof Error ? error.message : String(error)}`);
+    }
+  }
+
+  /**
+   * List a tenant's or provider's leases
+   * @param owner Tenant address or provider ID
+   * @param cursor Offset into the owner's leases
+   * @param limit Maximum number of leases to return
+   * @returns List of lease IDs and leases
+   */
+  public async listLeases(
+    owner: LeaseOwner,
+    cursor: number,
+    limit: number
+  ): Promise<Array<[number, Lease]>> {
+    try {
+      logger.info(`Listing leases of ${owner[0]} ${owner[1]}`);
+      const contract = sorobanClient.getContract(this.contractId);
+      
+      const result = await sorobanClient.callMethod<Array<[number, Lease]>>(
+        contract,
+        'list_leases',
+        [leaseOwnerToScVal(owner),
+        nativeToScVal(cursor, {type: "u32"}),
+        nativeToScVal(limit, {type: "u32"})]
+      );
+      
+      logger.info(`Retrieved ${result.length} leases`);
+      return result;
+    } catch (error) {
+      logger.error(`Failed to list leases of ${owner[0]} ${owner[1]}:`, error);
+      throw new Error(`List leases failed: ${error instanceof Error ? error.message : String(error)}`);
+    }
+  }
+
+  /**
+   * Get the leases an order was filled with
+   * @param orderId Order ID
+   * @returns List of lease IDs and leases
+   */
+  public async getLeasesByOrder(orderId: number): Promise<Array<[number, Lease]>> {
+    try {
+      logger.info(`Getting leases for order ${orderId}`);
+      const contract = sorobanClient.getContract(this.contractId);
+      
+      const result = await sorobanClient.callMethod<Array<[number, Lease]>>(
+        contract,
+        'get_lease_by_order',
+        nativeToScVal(orderId, { type: "u64"})
+      );
+      
+      logger.info(`Retrieved ${result.length} leases for order ${orderId}`);
+      return result;
+    } catch (error) {
+      logger.error(`Failed to get leases for order ${orderId}:`, error);
+      throw new Error(`Get leases failed: ${error instanceof Error ? error.message : String(error)}`);
+    }
+  }
+
+  /**
+   * Complete a lease that ran its term and pay the provider
+   * @param leaseId Lease ID
+   */
+  public async completeLease(leaseId: number): Promise<void> {
+    try {
+      logger.info(`Completing lease with ID ${leaseId}`);
+      const contract = sorobanClient.getContract(this.contractId);
+      
+      await sorobanClient.callMethod<void>(
+        contract,
+        'complete_lease',
+        nativeToScVal(leaseId, { type: "u64"})
+      );
+      
+      logger.info(`Lease ${leaseId} completed`);
+    } catch (error) {
+      logger.error(`Failed to complete lease ${leaseId}:`, error);
+      throw new Error(`Complete lease failed: ${error instanceof Error ? error.message : String(error)}`);
+    }
+  }
+
+  /**
+   * Pay the provider the earnings a lease has accrued so far
+   * @param leaseId Lease ID
+   * @returns Amount claimed
+   */
+  public async claimLeaseEarnings(leaseId: number): Promise<string> {
+    try {
+      logger.info(`Claiming earnings for lease with ID ${leaseId}`);
+      const contract = sorobanClient.getContract(this.contractId);
+
+      const result = await sorobanClient.callMethod<bigint>(
+        contract,
+        'claim_lease_earnings',
+        nativeToScVal(leaseId, { type: "u64"})
+      );
+
+      logger.info(`Claimed ${result} for lease ${leaseId}`);
+      return String(result);
+    } catch (error) {
+      logger.error(`Failed to claim earnings for lease ${leaseId}:`, error);
+      throw new Error(`Claim lease earnings failed: ${error instanceof Error ? error.message : String(error)}`);
+    }
+  }
+
+  /**
+   * Cancel a lease
+   * @param from Address of the tenant or provider canceling
+   * @param leaseId Lease ID
+   */
+  public async cancelLease(from: string, leaseId: number): Promise<void> {
+    try {
+      logger.info(`Canceling lease with ID ${leaseId}`);
+      const contract = sorobanClient.getContract(this.contractId);
+      
+      await sorobanClient.callMethod<void>(
+        contract,
+        'cancel_lease',
+        [addressToScVal(from),
+        nativeToScVal(leaseId, { type: "u64"})]
+      );
+      
+      logger.info(`Lease ${leaseId} canceled`);
+    } catch (error) {
+      logger.error(`Failed to cancel lease ${leaseId}:`, error);
+      throw new Error(`Cancel lease failed: ${error instanceof Error ? error.message : String(error)}`);
+    }
+  }
 }
 
 // Export singleton instance
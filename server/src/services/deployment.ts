@@ -302,15 +302,6 @@ export class DeploymentService {
       
       // Log the penalty
       logger.warn(`Provider ${providerId} (${provider.address}) penalized for unhealthy deployment of order ${orderId}. Would lock ${order.maxPrice} from provider's balance.`);
-      
-      // In a real implementation with appropriate keypairs:
-      // const adminKeypair = Keypair.fromSecret(config.soroban.signerSecretKey);
-      // await escrowContract.lock(
-      //   "TOKEN_ADDRESS",
-      //   provider.address,
-      //   order.maxPrice,
-      //   adminKeypair
-      // );
     } catch (error) {
       logger.error(`Failed to penalize provider ${providerId}:`, error);
     }
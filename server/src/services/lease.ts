@@ -1,6 +1,7 @@
-import { Keypair } from '@stellar/stellar-sdk';
-import { LeaseModel } from '../types/models';
-import { LeaseState } from '../types/contracts';
+import { sorobanClient } from '../contracts/client';
+import { orderBookContract } from '../contracts/order-book';
+import { LeaseModel, PaymentModel } from '../types/models';
+import { Lease } from '../types/contracts';
 import { deploymentService } from './deployment';
 import { paymentService } from './payment';
 import { providerService } from './provider';
@@ -9,12 +10,10 @@ import { createLogger } from '../utils/logger';
 const logger = createLogger('lease-service');
 
 /**
- * Lease service for managing container leases
+ * Lease service for managing container leases. Leases are read from the
+ * order book; only their deployments are tracked here.
  */
 export class LeaseService {
-  private leases: Map<number, LeaseModel> = new Map();
-  private leaseCounter: number = 0;
-
   /**
    * Initialize the lease service
    */
@@ -23,84 +22,30 @@ export class LeaseService {
   }
 
   /**
-   * Create a new lease
-   * @param orderId Order ID
-   * @param providerId Provider ID
-   * @param startBlock Start block
-   * @param endBlock End block
-   * @param tenantAddress Tenant address
+   * Deploy the container for a lease created on chain
+   * @param leaseId Lease ID
    * @returns Lease model
    */
-  public async createLease(
-    orderId: number,
-    providerId: number,
-    startBlock: number,
-    endBlock: number,
-    tenantAddress: string
-  ): Promise<LeaseModel> {
+  public async createLease(leaseId: number): Promise<LeaseModel> {
     try {
-      logger.info(`Creating lease for order ${orderId} with provider ${providerId}`);
-      
-      const provider = await providerService.getProvider(providerId);
+      logger.info(`Deploying lease ${leaseId}`);
       
-      // Calculate estimated end time (assuming 5 second block time)
-      const blocksRemaining = endBlock - startBlock;
-      const secondsRemaining = blocksRemaining * 5;
-      const estimatedEndTime = new Date();
-      estimatedEndTime.setSeconds(estimatedEndTime.getSeconds() + secondsRemaining);
-      
-      // Create lease model
-      const leaseId = ++this.leaseCounter;
-      const lease: LeaseModel = {
-        id: leaseId,
-        orderId,
-        providerId,
-        startBlock,
-        endBlock,
-        estimatedEndTime,
-        state: LeaseState.Active,
-        createdAt: new Date(),
-        updatedAt: new Date(),
-      };
-      
-      // Store lease
-      this.leases.set(leaseId, lease);
-      
-      logger.info(`Lease created with ID ${leaseId}`);
+      const onChainLease = await orderBookContract.getLease(leaseId);
+      const lease = await this.toLeaseModel(leaseId, onChainLease);
       
       // Deploy container for this lease
       try {
-        const deployment = await deploymentService.deployContainer(lease);
-        
-        // Update lease with container details
-        lease.containerId = deployment.containerId;
-        
-        // Set container access URL based on provider's hostname/port
-        if (provider.hostname) {
-          // Assuming first port mapping is the main service port
-          const firstPort = deployment.imageId.includes(':') ? 
-            deployment.imageId.split(':')[1] : 
-            '80';
-            
-          lease.accessUrl = `http://${provider.hostname}:${firstPort}`;
-        }
-        
-        lease.updatedAt = new Date();
-        this.leases.set(leaseId, lease);
-        
+        await deploymentService.deployContainer(lease);
         logger.info(`Container deployed for lease ${leaseId}`);
       } catch (deployError) {
+        // Don't fail the lease, it stays active with deployment issues
         logger.error(`Failed to deploy container for lease ${leaseId}:`, deployError);
-        
-        // Set lease to problem state but don't fail the lease creation
-        lease.state = LeaseState.Active; // Still active, but with deployment issues
-        lease.updatedAt = new Date();
-        this.leases.set(leaseId, lease);
       }
       
-      return lease;
+      // Pick up the container details
+      return this.toLeaseModel(leaseId, onChainLease);
     } catch (error) {
-      logger.error(`Failed to create lease for order ${orderId}:`, error);
+      logger.error(`Failed to create lease ${leaseId}:`, error);
       throw new Error(`Lease creation failed: ${error instanceof Error ? error.message : String(error)}`);
     }
   }
@@ -114,22 +59,13 @@ export class LeaseService {
     try {
       logger.info(`Completing lease with ID ${leaseId}`);
       
-      // Get lease
-      const lease = this.leases.get(leaseId);
-      
-      if (!lease) {
-        throw new Error(`Lease ${leaseId} not found`);
-      }
+      // Complete lease on chain
+      await orderBookContract.completeLease(leaseId);
       
       // Stop container
       await deploymentService.stopContainer(leaseId);
       
-      // Update lease
-      lease.state = LeaseState.Completed;
-      lease.updatedAt = new Date();
-      
-      // Store updated lease
-      this.leases.set(leaseId, lease);
+      const lease = await this.toLeaseModel(leaseId, await orderBookContract.getLease(leaseId));
       
       logger.info(`Lease ${leaseId} completed`);
       return lease;
@@ -148,22 +84,13 @@ export class LeaseService {
     try {
       logger.info(`Canceling lease with ID ${leaseId}`);
       
-      // Get lease
-      const lease = this.leases.get(leaseId);
-      
-      if (!lease) {
-        throw new Error(`Lease ${leaseId} not found`);
-      }
+      // Cancel lease on chain
+      await orderBookContract.cancelLease(await sorobanClient.getAddress(), leaseId);
       
       // Stop container
       await deploymentService.stopContainer(leaseId);
       
-      // Update lease
-      lease.state = LeaseState.Canceled;
-      lease.updatedAt = new Date();
-      
-      // Store updated lease
-      this.leases.set(leaseId, lease);
+      const lease = await this.toLeaseModel(leaseId, await orderBookContract.getLease(leaseId));
       
       logger.info(`Lease ${leaseId} canceled`);
       return lease;
@@ -174,47 +101,30 @@ export class LeaseService {
   }
 
   /**
-   * Process payment for a lease
+   * Process payment for a lease by claiming the earnings it has accrued
    * @param leaseId Lease ID
-   * @param tokenAddress Token address
-   * @param tenantAddress Tenant address
-   * @param amount Amount to pay
-   * @param adminKeypair Admin keypair for authentication
-   * @returns Success boolean
+   * @returns Payment model
    */
-  public async processPayment(
-    leaseId: number,
-    tokenAddress: string,
-    tenantAddress: string,
-    amount: string,
-    adminKeypair: Keypair
-  ): Promise<boolean> {
+  public async processPayment(leaseId: number): Promise<PaymentModel> {
     try {
       logger.info(`Processing payment for lease ${leaseId}`);
       
       // Get lease
-      const lease = this.leases.get(leaseId);
-      
-      if (!lease) {
-        throw new Error(`Lease ${leaseId} not found`);
-      }
+      const lease = await orderBookContract.getLease(leaseId);
       
       // Get provider
-      const provider = await providerService.getProvider(lease.providerId);
+      const provider = await providerService.getProvider(lease.provider_id);
       
-      // Transfer funds from tenant to provider
-      await paymentService.transferFunds(
-        tokenAddress,
-        tenantAddress,
-        provider.address,
-        lease.providerId,
-        amount,
+      // Pay the provider out of the tenant's locked funds
+      const payment = await paymentService.claimLeaseEarnings(
         leaseId,
-        adminKeypair
+        lease.token,
+        lease.tenant,
+        provider.address
       );
       
       logger.info(`Payment processed for lease ${leaseId}`);
-      return true;
+      return payment;
     } catch (error) {
       logger.error(`Failed to process payment for lease ${leaseId}:`, error);
       throw new Error(`Process payment failed: ${error instanceof Error ? error.message : String(error)}`);
@@ -226,25 +136,38 @@ export class LeaseService {
    * @param leaseId Lease ID
    * @returns Lease model
    */
-  public getLease(leaseId: number): LeaseModel | undefined {
-    return this.leases.get(leaseId);
+  public async getLease(leaseId: number): Promise<LeaseModel> {
+    return this.toLeaseModel(leaseId, await orderBookContract.getLease(leaseId));
   }
 
   /**
-   * List all leases
+   * List the tenant's leases
+   * @param cursor Offset into the tenant's leases
+   * @param limit Maximum number of leases to return
    * @returns List of lease models
    */
-  public listLeases(): LeaseModel[] {
-    return Array.from(this.leases.values());
+  public async listLeases(cursor: number, limit: number): Promise<LeaseModel[]> {
+    const tenantAddress = await sorobanClient.getAddress();
+    return this.toLeaseModels(
+      await orderBookContract.listLeases(['Tenant', tenantAddress], cursor, limit)
+    );
   }
 
   /**
    * List leases for a provider
    * @param providerId Provider ID
+   * @param cursor Offset into the provider's leases
+   * @param limit Maximum number of leases to return
    * @returns List of lease models
    */
-  public listLeasesForProvider(providerId: number): LeaseModel[] {
-    return Array.from(this.leases.values()).filter(lease => lease.providerId === providerId);
+  public async listLeasesForProvider(
+    providerId: number,
+    cursor: number,
+    limit: number
+  ): Promise<LeaseModel[]> {
+    return this.toLeaseModels(
+      await orderBookContract.listLeases(['Provider', providerId], cursor, limit)
+    );
   }
 
   /**
@@ -252,8 +175,8 @@ export class LeaseService {
    * @param orderId Order ID
    * @returns List of lease models
    */
-  public listLeasesForOrder(orderId: number): LeaseModel[] {
-    return Array.from(this.leases.values()).filter(lease => lease.orderId === orderId);
+  public async listLeasesForOrder(orderId: number): Promise<LeaseModel[]> {
+    return this.toLeaseModels(await orderBookContract.getLeasesByOrder(orderId));
   }
 
   /**
@@ -269,12 +192,8 @@ export class LeaseService {
     try {
       logger.info(`Checking health for lease ${leaseId}`);
       
-      // Get lease
-      const lease = this.leases.get(leaseId);
-      
-      if (!lease) {
-        throw new Error(`Lease ${leaseId} not found`);
-      }
+      // Fails if the lease does not exist
+      await orderBookContract.getLease(leaseId);
       
       // Check deployment health
       const deployment = await deploymentService.checkContainerHealth(leaseId);
@@ -308,12 +227,8 @@ export class LeaseService {
     try {
       logger.info(`Getting logs for lease ${leaseId}`);
       
-      // Get lease
-      const lease = this.leases.get(leaseId);
-      
-      if (!lease) {
-        throw new Error(`Lease ${leaseId} not found`);
-      }
+      // Fails if the lease does not exist
+      await orderBookContract.getLease(leaseId);
       
       // Get container logs
       const logs = await deploymentService.getContainerLogs(leaseId);
@@ -325,6 +240,60 @@ export class LeaseService {
       throw new Error(`Get logs failed: ${error instanceof Error ? error.message : String(error)}`);
     }
   }
+
+  /**
+   * Build lease models from on-chain leases
+   * @param leases Lease IDs and leases
+   * @returns List of lease models
+   */
+  private async toLeaseModels(leases: Array<[number, Lease]>): Promise<LeaseModel[]> {
+    return Promise.all(leases.map(([leaseId, lease]) => this.toLeaseModel(Number(leaseId), lease)));
+  }
+
+  /**
+   * Build a lease model from an on-chain lease and its deployment
+   * @param leaseId Lease ID
+   * @param lease On-chain lease
+   * @returns Lease model
+   */
+  private async toLeaseModel(leaseId: number, lease: Lease): Promise<LeaseModel> {
+    // Calculate estimated end time (assuming 5 second block time)
+    const secondsRemaining = (lease.end_block - lease.start_block) * 5;
+    const estimatedEndTime = new Date();
+    estimatedEndTime.setSeconds(estimatedEndTime.getSeconds() + secondsRemaining);
+    
+    const model: LeaseModel = {
+      id: leaseId,
+      orderId: Number(lease.order_id),
+      providerId: Number(lease.provider_id),
+      startBlock: lease.start_block,
+      endBlock: lease.end_block,
+      estimatedEndTime,
+      state: lease.state,
+      createdAt: new Date(),
+      updatedAt: new Date(),
+    };
+    
+    const deployment = deploymentService.getDeployment(leaseId);
+    if (deployment) {
+      model.containerId = deployment.containerId;
+      model.createdAt = deployment.createdAt;
+      model.updatedAt = deployment.updatedAt;
+      
+      // Set container access URL based on provider's hostname/port
+      const provider = await providerService.getProvider(model.providerId);
+      if (provider.hostname) {
+        // Assuming first port mapping is the main service port
+        const firstPort = deployment.imageId.includes(':') ? 
+          deployment.imageId.split(':')[1] : 
+          '80';
+          
+        model.accessUrl = `http://${provider.hostname}:${firstPort}`;
+      }
+    }
+    
+    return model;
+  }
 }
 
 // Export singleton instance
@@ -1,31 +1,46 @@
 import { sorobanClient } from '../contracts/client';
 import { orderBookContract } from '../contracts/order-book';
-import { BidState, OrderRequest, TrustLevel } from '../types/contracts';
+import { BidState, OrderFilter, OrderRequest, OrderSpec, OrderTerms, TrustLevel } from '../types/contracts';
 import { BidModel, ContainerSpecification, OrderModel } from '../types/models';
 import { createLogger } from '../utils/logger';
 
 const logger = createLogger('order-service');
 
+// Orders are open auctions without an SLA unless stated otherwise
+const defaultOrderTerms: OrderTerms = {
+  min_billing_blocks: 0,
+  sla: {
+    min_uptime_bps: 0,
+    max_latency_ms: 0,
+    downtime_budget: 0,
+    penalties: [],
+  },
+  class: ['Regular'],
+};
+
 /**
  * Order service for managing orders and bids
  */
 export class OrderService {
   /**
    * Create a new order
-   * @param tenantAddress Tenant address
+   * @param tokenAddress Token the order is priced in
    * @param maxPrice Maximum price
    * @param spec Container specification
    * @param trustLevels Required trust levels
    * @param quantity Number of instances
    * @param durationBlocks Duration in blocks
+   * @param bidDeadline Last block bids are accepted in
    * @returns Order model
    */
   public async createOrder(
+    tokenAddress: string,
     maxPrice: string,
     spec: ContainerSpecification,
     trustLevels: TrustLevel[],
     quantity: number,
-    durationBlocks: number
+    durationBlocks: number,
+    bidDeadline: number
   ): Promise<OrderModel> {
     try {
       const tenantAddress = await sorobanClient.getAddress()
@@ -41,11 +56,16 @@ export class OrderService {
       
       // Create order on chain
       const orderId = await orderBookContract.createOrder(
+        tenantAddress,
+        tokenAddress,
         maxPrice,
         durationBlocks,
         quantity,
-        specJson,
-        trustLevels
+        ['Text', specJson],
+        trustLevels,
+        bidDeadline,
+        ['Open'],
+        defaultOrderTerms
       );
       
       // Get created order
@@ -92,21 +112,7 @@ export class OrderService {
       // Get updated order
       const orderRequest = await orderBookContract.getOrder(orderId);
       
-      // Create order model
-      // In a real implementation, you would retrieve the full order details from a database
-      const orderModel: OrderModel = {
-        id: orderId,
-        tenantAddress: await sorobanClient.getAddress(), // Would be fetched from database
-        maxPrice: orderRequest.max_price,
-        specification: JSON.parse(orderRequest.spec.spec),
-        requiredTrustLevels: orderRequest.spec.trust_levels,
-        quantity: orderRequest.spec.quantity,
-        durationBlocks: orderRequest.number_of_blocks,
-        estimatedDurationHours: (orderRequest.number_of_blocks * 5) / 3600,
-        state: orderRequest.state,
-        createdAt: new Date(), // Would be fetched from database
-        updatedAt: new Date(),
-      };
+      const orderModel = this.toOrderModel(orderId, orderRequest);
       
       logger.info(`Order ${orderId} closed`);
       return orderModel;
@@ -128,34 +134,7 @@ export class OrderService {
       // Get order from chain
       const orderRequest = await orderBookContract.getOrder(orderId);
       
-      // Parse specification
-      let specification: ContainerSpecification;
-      try {
-        specification = JSON.parse(orderRequest.spec.spec);
-      } catch (error) {
-        logger.error(`Failed to parse specification for order ${orderId}:`, error);
-        specification = {
-          image: 'unknown',
-          cpu: 0,
-          memory: 0,
-          storage: 0,
-        };
-      }
-      
-      // Create order model
-      const orderModel: OrderModel = {
-        id: orderId,
-        tenantAddress: await sorobanClient.getAddress(), // Would be fetched from database
-        maxPrice: orderRequest.max_price,
-        specification,
-        requiredTrustLevels: orderRequest.spec.trust_levels,
-        quantity: orderRequest.spec.quantity,
-        durationBlocks: orderRequest.number_of_blocks,
-        estimatedDurationHours: (orderRequest.number_of_blocks * 5) / 3600,
-        state: orderRequest.state,
-        createdAt: new Date(), // Would be fetched from database
-        updatedAt: new Date(),
-      };
+      const orderModel = this.toOrderModel(orderId, orderRequest);
       
       logger.info(`Order ${orderId} retrieved`);
       return orderModel;
@@ -166,46 +145,23 @@ export class OrderService {
   }
 
   /**
-   * List all orders
+   * List orders matching a filter
+   * @param filter Order filter
+   * @param cursor Order ID to continue after, 0 for the first page
+   * @param limit Maximum number of orders to return
    * @returns List of order models
    */
-  public async listOrders(): Promise<OrderModel[]> {
+  public async listOrders(filter: OrderFilter, cursor: number, limit: number): Promise<OrderModel[]> {
     try {
-      logger.info('Listing all orders');
+      logger.info(`Listing orders after ${cursor}`);
       
       // Get orders from chain
-      const orderRequests = await orderBookContract.listOrders();
+      const orderRequests = await orderBookContract.listOrders(filter, cursor, limit);
       
       // Create order models
-      const orderModels: OrderModel[] = orderRequests.map((orderRequest, index) => {
-        // Parse specification
-        let specification: ContainerSpecification;
-        try {
-          specification = JSON.parse(orderRequest.spec.spec);
-        } catch (error) {
-          logger.error(`Failed to parse specification for order ${index + 1}:`, error);
-          specification = {
-            image: 'unknown',
-            cpu: 0,
-            memory: 0,
-            storage: 0,
-          };
-        }
-        
-        return {
-          id: index + 1, // This is an approximation; in a real system, you'd map the actual IDs
-          tenantAddress: '', // Would be fetched from database
-          maxPrice: orderRequest.max_price,
-          specification,
-          requiredTrustLevels: orderRequest.spec.trust_levels,
-          quantity: orderRequest.spec.quantity,
-          durationBlocks: orderRequest.number_of_blocks,
-          estimatedDurationHours: (orderRequest.number_of_blocks * 5) / 3600,
-          state: orderRequest.state,
-          createdAt: new Date(), // Would be fetched from database
-          updatedAt: new Date(),
-        };
-      });
+      const orderModels: OrderModel[] = orderRequests.map(([orderId, orderRequest]) =>
+        this.toOrderModel(Number(orderId), orderRequest)
+      );
       
       logger.info(`Retrieved ${orderModels.length} orders`);
       return orderModels;
@@ -219,19 +175,21 @@ export class OrderService {
    * Place a bid on an order
    * @param orderId Order ID
    * @param providerId Provider ID
-   * @param bidPrice Bid price
+   * @param bidPrice Bid price per instance
+   * @param quantity Number of instances bid for
    * @returns Bid model
    */
   public async placeBid(
     orderId: number,
     providerId: number,
-    bidPrice: string
+    bidPrice: string,
+    quantity: number
   ): Promise<BidModel> {
     try {
       logger.info(`Placing bid on order ${orderId} by provider ${providerId} for ${bidPrice}`);
       
       // Place bid on chain
-      const bidId = await orderBookContract.placeBid(orderId, providerId, bidPrice);
+      const bidId = await orderBookContract.placeBid(orderId, providerId, bidPrice, quantity);
       
       // Create bid model
       const bidModel: BidModel = {
@@ -239,6 +197,7 @@ export class OrderService {
         orderId,
         providerId,
         price: bidPrice,
+        quantity,
         state: BidState.Active,
         createdAt: new Date(),
         updatedAt: new Date(),
@@ -271,6 +230,7 @@ export class OrderService {
         orderId: 0, // Would be fetched from database
         providerId: 0, // Would be fetched from database
         price: '0', // Would be fetched from database
+        quantity: 0, // Would be fetched from database
         state: BidState.Matched,
         createdAt: new Date(), // Would be fetched from database
         updatedAt: new Date(),
@@ -287,18 +247,25 @@ export class OrderService {
   /**
    * Get bids for an order
    * @param orderId Order ID
+   * @param cursor Offset into the order's bids
+   * @param limit Maximum number of bids to return
    * @returns List of bid models
    */
-  public async getBidsForOrder(orderId: number): Promise<BidModel[]> {
+  public async getBidsForOrder(orderId: number, cursor: number, limit: number): Promise<BidModel[]> {
     try {
       logger.info(`Getting bids for order ${orderId}`);
       
-      // Note: This would require a database query in a real implementation
-      // since the contract doesn't provide a direct way to list bids for an order
-      
-      // Placeholder: In a real implementation, you would query a database for bids
-      // where orderId matches the given orderId
-      const bids: BidModel[] = [];
+      const providerBids = await orderBookContract.listBidsForOrder(orderId, cursor, limit);
+      const bids: BidModel[] = providerBids.map(([bidId, bid]) => ({
+        id: Number(bidId),
+        orderId: Number(bid.order_id),
+        providerId: Number(bid.provider),
+        price: String(bid.bid_price),
+        quantity: Number(bid.quantity),
+        state: bid.state,
+        createdAt: new Date(), // Would be fetched from database
+        updatedAt: new Date(),
+      }));
       
       logger.info(`Retrieved ${bids.length} bids for order ${orderId}`);
       return bids;
@@ -307,6 +274,58 @@ export class OrderService {
       throw new Error(`Get bids failed: ${error instanceof Error ? error.message : String(error)}`);
     }
   }
+
+  /**
+   * Build an order model from an on-chain order
+   * @param orderId Order ID
+   * @param orderRequest On-chain order
+   * @returns Order model
+   */
+  private toOrderModel(orderId: number, orderRequest: OrderRequest): OrderModel {
+    return {
+      id: orderId,
+      tenantAddress: orderRequest.tenant,
+      maxPrice: orderRequest.max_price,
+      specification: this.parseSpecification(orderId, orderRequest.spec.spec),
+      requiredTrustLevels: orderRequest.spec.trust_levels,
+      quantity: orderRequest.spec.quantity,
+      durationBlocks: orderRequest.number_of_blocks,
+      estimatedDurationHours: (orderRequest.number_of_blocks * 5) / 3600,
+      state: orderRequest.state,
+      createdAt: new Date(), // Would be fetched from database
+      updatedAt: new Date(),
+    };
+  }
+
+  /**
+   * Read the container specification of an order
+   * @param orderId Order ID
+   * @param spec Free-form or typed resource specification
+   * @returns Container specification
+   */
+  private parseSpecification(orderId: number, spec: OrderSpec): ContainerSpecification {
+    if (spec[0] === 'Resources') {
+      const resources = spec[1];
+      return {
+        image: Buffer.from(resources.image_digest).toString('hex'),
+        cpu: resources.cpu_millis / 1000,
+        memory: Number(resources.memory_mb),
+        storage: Number(resources.storage_gb),
+      };
+    }
+
+    try {
+      return JSON.parse(spec[1]);
+    } catch (error) {
+      logger.error(`Failed to parse specification for order ${orderId}:`, error);
+      return {
+        image: 'unknown',
+        cpu: 0,
+        memory: 0,
+        storage: 0,
+      };
+    }
+  }
 }
 
 // Export singleton instance
@@ -1,5 +1,6 @@
 import { Keypair } from '@stellar/stellar-sdk';
 import { escrowContract } from '../contracts/escrow';
+import { orderBookContract } from '../contracts/order-book';
 import { PaymentModel } from '../types/models';
 import { createLogger } from '../utils/logger';
 
@@ -71,87 +72,24 @@ export class PaymentService {
   }
 
   /**
-   * Lock funds for an order
-   * @param tokenAddress Token address
-   * @param tenantAddress Tenant address
-   * @param amount Amount to lock
-   * @param tenantKeypair Tenant keypair for authentication
-   * @returns Payment model
-   */
-  public async lockFunds(
-    tokenAddress: string,
-    tenantAddress: string,
-    amount: string,
-    tenantKeypair: Keypair
-  ): Promise<PaymentModel> {
-    try {
-      logger.info(`Locking ${amount} from tenant ${tenantAddress}`);
-      
-      // Lock funds in escrow
-      await escrowContract.lock(
-        tokenAddress,
-        tenantAddress,
-        amount,
-        tenantKeypair
-      );
-      
-      // Create payment model
-      const paymentId = ++this.paymentCounter;
-      const payment: PaymentModel = {
-        id: paymentId,
-        leaseId: 0, // No lease associated yet
-        tenantAddress,
-        providerAddress: '', // No provider yet
-        amount,
-        tokenAddress,
-        status: 'pending',
-        txHash: '', // Would be set in a real implementation
-        createdAt: new Date(),
-        updatedAt: new Date(),
-      };
-      
-      // Store payment
-      this.payments.set(paymentId, payment);
-      
-      logger.info(`Locked ${amount} from tenant ${tenantAddress}`);
-      return payment;
-    } catch (error) {
-      logger.error(`Failed to lock funds from tenant ${tenantAddress}:`, error);
-      throw new Error(`Lock failed: ${error instanceof Error ? error.message : String(error)}`);
-    }
-  }
-
-  /**
-   * Transfer locked funds to a provider
+   * Claim the earnings a lease has accrued for its provider
+   * @param leaseId Lease ID
    * @param tokenAddress Token address
    * @param tenantAddress Tenant address
    * @param providerAddress Provider address
-   * @param providerId Provider ID
-   * @param amount Amount to transfer
-   * @param leaseId Lease ID
-   * @param adminKeypair Admin keypair for authentication
    * @returns Payment model
    */
-  public async transferFunds(
+  public async claimLeaseEarnings(
+    leaseId: number,
     tokenAddress: string,
     tenantAddress: string,
-    providerAddress: string,
-    providerId: number,
-    amount: string,
-    leaseId: number,
-    adminKeypair: Keypair
+    providerAddress: string
   ): Promise<PaymentModel> {
     try {
-      logger.info(`Transferring ${amount} from tenant ${tenantAddress} to provider ${providerId}`);
+      logger.info(`Claiming earnings for lease ${leaseId}`);
       
-      // Transfer locked funds to provider
-      await escrowContract.transferLocked(
-        tokenAddress,
-        tenantAddress,
-        amount,
-        providerId,
-        adminKeypair
-      );
+      // The order book moves the tenant's locked funds to the provider
+      const amount = await orderBookContract.claimLeaseEarnings(leaseId);
       
       // Create payment model
       const paymentId = ++this.paymentCounter;
@@ -171,11 +109,11 @@ export class PaymentService {
       // Store payment
       this.payments.set(paymentId, payment);
       
-      logger.info(`Transferred ${amount} from tenant ${tenantAddress} to provider ${providerId}`);
+      logger.info(`Claimed ${amount} for lease ${leaseId}`);
       return payment;
     } catch (error) {
-      logger.error(`Failed to transfer funds from tenant ${tenantAddress} to provider ${providerId}:`, error);
-      throw new Error(`Transfer failed: ${error instanceof Error ? error.message : String(error)}`);
+      logger.error(`Failed to claim earnings for lease ${leaseId}:`, error);
+      throw new Error(`Claim failed: ${error instanceof Error ? error.message : String(error)}`);
     }
   }
 
@@ -25,12 +25,17 @@ export enum TrustLevel {
     Active = 'Active',
     Canceled = 'Canceled',
     Matched = 'Matched',
+    Committed = 'Committed',
+    Void = 'Void',
   }
   
   export enum LeaseState {
+    Initiate = 'Initiate',
     Active = 'Active',
     Canceled = 'Canceled',
     Completed = 'Completed',
+    Ended = 'Ended',
+    Disputed = 'Disputed',
   }
   
   // Provider Registry Contract Interfaces
@@ -41,35 +46,127 @@ export enum TrustLevel {
   }
   
   // OrderBook Contract Interfaces
+  // Contract enums with data are encoded as [variant, value] tuples
+  export interface ResourceSpec {
+    cpu_millis: number;
+    memory_mb: number;
+    storage_gb: number;
+    gpus: number;
+    region: string;
+    image_digest: Buffer; // 32 bytes
+    ports: number[];
+    capabilities: string[];
+  }
+  
+  export type OrderSpec = ['Text', string] | ['Resources', ResourceSpec];
+  
+  export interface DutchAuction {
+    floor_price: string; // Using string for large numbers
+    decay_blocks: number;
+  }
+  
+  export type AuctionMode = ['Open'] | ['Sealed', number] | ['Dutch', DutchAuction];
+  
+  export interface SlaPenalty {
+    downtime_blocks: number;
+    refund_bps: number;
+  }
+  
+  export interface SlaTerms {
+    min_uptime_bps: number;
+    max_latency_ms: number;
+    downtime_budget: number;
+    penalties: SlaPenalty[];
+  }
+  
+  export interface ForwardTerms {
+    start_block: number;
+    deposit_bps: number;
+    funding_blocks: number;
+  }
+  
+  export type OrderClass = ['Regular'] | ['Spot'] | ['Forward', ForwardTerms];
+  
+  export interface OrderTerms {
+    min_billing_blocks: number;
+    sla: SlaTerms;
+    class: OrderClass;
+  }
+  
+  // Empty states match every state and unset fields match every order
+  export interface OrderFilter {
+    states: OrderState[];
+    tenant?: string;
+    token?: string;
+    min_price?: string; // Using string for large numbers
+    max_price?: string; // Using string for large numbers
+  }
+  
   export interface Specification {
-    spec: string;
+    spec: OrderSpec;
     trust_levels: TrustLevel[];
     quantity: number;
     max_price: string; // Using string for large numbers
   }
   
   export interface OrderRequest {
+    tenant: string;
+    token: string;
     max_price: string; // Using string for large numbers
     state: OrderState;
     spec: Specification;
     number_of_blocks: number;
+    bid_deadline: number;
+    auction: AuctionMode;
+    filled: number;
+    terms: OrderTerms;
+    created_at: number;
   }
   
   export interface ProviderBid {
     order_id: number;
     provider: number;
     bid_price: string; // Using string for large numbers
+    quantity: number;
     state: BidState;
+    placed_at: number;
+    commitment?: Buffer;
+    bond: string; // Using string for large numbers
+  }
+  
+  export interface AutoRenew {
+    extra_blocks: number;
+    renewals_left: number;
   }
   
   export interface Lease {
     order_id: number;
+    bid_id: number;
     provider_id: number;
+    tenant: string;
+    token: string;
+    price: string; // Using string for large numbers
+    claimed: string; // Using string for large numbers
     start_block: number;
     end_block: number;
     state: LeaseState;
+    container?: string;
+    auto_renew: AutoRenew;
+    sla: SlaTerms;
+    dispute_window: number;
+    evidence?: Buffer;
+    successor_order?: number;
+    manifest_hash?: Buffer;
+    secrets_uri?: string;
+    secrets_key?: Buffer;
+    deployed_manifest?: Buffer;
+    class: OrderClass;
+    unfunded: string; // Using string for large numbers
+    deposit: string; // Using string for large numbers
   }
   
+  export type LeaseOwner = ['Tenant', string] | ['Provider', number];
+  
   // Escrow Contract Interfaces
   export interface Tenant {
     locked_balance: string; // Using string for large numbers
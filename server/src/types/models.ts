@@ -67,6 +67,7 @@ import {
     orderId: number;
     providerId: number;
     price: string;
+    quantity: number;
     state: BidState;
     createdAt: Date;
     updatedAt: Date;